@@ -1,16 +1,24 @@
-use std::collections::HashMap;
 use std::collections::HashSet;
+use std::env;
 use std::fs;
 
-fn parse_puzzle_input() -> Vec<Vec<u8>> {
+// Heights are digits, '.' marks a tile that can't be walked on
+fn parse_puzzle_input() -> Vec<Vec<Option<u8>>> {
     let input = fs::read_to_string("puzzle_input.txt").expect("There is no input file?");
 
-    let data: Vec<Vec<u8>> = input
+    let data: Vec<Vec<Option<u8>>> = input
         .lines()
         .filter(|line| !line.trim().is_empty())
-        .map(|line| {
+        .enumerate()
+        .map(|(i, line)| {
             line.chars()
-                .filter_map(|c| c.to_digit(10).map(|d| d as u8))
+                .enumerate()
+                .map(|(j, c)| match c {
+                    '.' => None,
+                    _ => Some(c.to_digit(10).unwrap_or_else(|| {
+                        panic!("Unexpected character {:?} at row {}, column {}", c, i, j)
+                    }) as u8),
+                })
                 .collect()
         })
         .collect();
@@ -18,21 +26,27 @@ fn parse_puzzle_input() -> Vec<Vec<u8>> {
     data
 }
 
-fn print_map(data: &Vec<Vec<u8>>) {
-    for i in 0..data.len() {
-        for j in 0..data[i].len() {
-            print!("{}", data[i][j])
-        }
-        println!();
-    }
+// Trails climb one height per step from start_height up to summit_height
+#[derive(Debug, Clone, Copy)]
+struct TrailConfig {
+    start_height: u8,
+    summit_height: u8,
 }
 
-fn find_trail_heads(data: &Vec<Vec<u8>>) -> HashSet<(usize, usize)> {
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TrailScore {
+    // Distinct summits reachable from the trailhead
+    score: usize,
+    // Distinct hiking trails starting at the trailhead
+    rating: u64,
+}
+
+fn find_trail_heads(data: &[Vec<Option<u8>>], config: TrailConfig) -> HashSet<(usize, usize)> {
     let mut heads = HashSet::new();
 
-    for i in 0..data.len() {
-        for j in 0..data[i].len() {
-            if data[i][j] == 0 {
+    for (i, row) in data.iter().enumerate() {
+        for (j, &height) in row.iter().enumerate() {
+            if height == Some(config.start_height) {
                 heads.insert((i, j));
             }
         }
@@ -40,70 +54,189 @@ fn find_trail_heads(data: &Vec<Vec<u8>>) -> HashSet<(usize, usize)> {
     heads
 }
 
-fn walk_trail(
-    data: &Vec<Vec<u8>>,
-    reachable_summits: &mut HashSet<(usize, usize)>,
-    i: usize,
-    j: usize,
-) -> u64 {
-    let current_height = data[i][j];
-    let mut count = 0;
-
-    if current_height == 9 {
-        reachable_summits.insert((i, j));
-        return 1;
-    } else {
-        let max_i = data.len() - 1;
-        let max_j = data[0].len() - 1;
-        let mut next_steps: Vec<(usize, usize)> = Vec::new();
-
-        if i > 0 {
-            next_steps.push((i - 1, j));
-        }
-        if i < max_i {
-            next_steps.push((i + 1, j));
-        }
-        if j > 0 {
-            next_steps.push((i, j - 1));
-        }
-        if j < max_j {
-            next_steps.push((i, j + 1));
+fn neighbors(data: &[Vec<Option<u8>>], i: usize, j: usize) -> Vec<(usize, usize)> {
+    let max_i = data.len() - 1;
+    let max_j = data[0].len() - 1;
+    let mut next_steps: Vec<(usize, usize)> = Vec::new();
+
+    if i > 0 {
+        next_steps.push((i - 1, j));
+    }
+    if i < max_i {
+        next_steps.push((i + 1, j));
+    }
+    if j > 0 {
+        next_steps.push((i, j - 1));
+    }
+    if j < max_j {
+        next_steps.push((i, j + 1));
+    }
+    next_steps
+}
+
+// Dynamic programming from the summits down: a cell's rating is the sum of
+// the ratings of its uphill neighbours, and its reachable summits are the
+// union of theirs (one bit per summit). Every cell is visited once.
+fn calculate_trail_scores(data: &[Vec<Option<u8>>], config: TrailConfig) -> Vec<Vec<TrailScore>> {
+    let height = data.len();
+    let width = data[0].len();
+    let TrailConfig {
+        start_height,
+        summit_height,
+    } = config;
+
+    let mut cells_by_height: Vec<Vec<(usize, usize)>> = vec![Vec::new(); 10];
+    // Position of each cell within its height's list
+    let mut layer_index: Vec<Vec<usize>> = vec![vec![0; width]; height];
+    for (i, row) in data.iter().enumerate() {
+        for (j, &h) in row.iter().enumerate() {
+            let Some(h) = h else { continue };
+            layer_index[i][j] = cells_by_height[h as usize].len();
+            cells_by_height[h as usize].push((i, j));
         }
+    }
+
+    // Summits are numbered by their position in the top layer
+    let words = cells_by_height[summit_height as usize].len().div_ceil(64);
+    let mut ratings: Vec<Vec<u64>> = vec![vec![0; width]; height];
+    let mut scores: Vec<Vec<usize>> = vec![vec![0; width]; height];
+    // Summit bitsets of the layer above, `words` words per cell; only two
+    // layers are ever needed at once
+    let mut upper: Vec<u64> = Vec::new();
+
+    for h in (start_height..=summit_height).rev() {
+        let cells = &cells_by_height[h as usize];
+        let mut current: Vec<u64> = vec![0; cells.len() * words];
 
-        for (x, y) in next_steps {
-            if current_height + 1 == data[x][y] {
-                count += walk_trail(data, reachable_summits, x, y);
+        for (k, (&(i, j), bits)) in cells.iter().zip(current.chunks_mut(words)).enumerate() {
+            if h == summit_height {
+                bits[k / 64] |= 1 << (k % 64);
+                ratings[i][j] = 1;
+            } else {
+                for (x, y) in neighbors(data, i, j) {
+                    if data[x][y] == Some(h + 1) {
+                        ratings[i][j] += ratings[x][y];
+                        let uphill = layer_index[x][y] * words;
+                        for (word, above) in bits.iter_mut().zip(&upper[uphill..uphill + words]) {
+                            *word |= above;
+                        }
+                    }
+                }
             }
+            scores[i][j] = bits.iter().map(|w| w.count_ones() as usize).sum();
         }
+        upper = current;
     }
-    count
+
+    (0..height)
+        .map(|i| {
+            (0..width)
+                .map(|j| TrailScore {
+                    score: scores[i][j],
+                    rating: ratings[i][j],
+                })
+                .collect()
+        })
+        .collect()
 }
 
-fn calculate_trail_scores(
-    data: &Vec<Vec<u8>>,
-    heads: &HashSet<(usize, usize)>,
-) -> HashMap<(usize, usize), usize> {
-    let mut trail_scores: HashMap<(usize, usize), usize> = HashMap::new();
-    let mut sum_of_routes: u64 = 0;
-
-    for &(i, j) in heads {
-        let mut reachable_summits: HashSet<(usize, usize)> = HashSet::new();
-        sum_of_routes += walk_trail(data, &mut reachable_summits, i, j);
-        trail_scores.insert((i, j), reachable_summits.len());
+// Every trail from `head` to a summit, as a list of coordinates
+fn list_trails(
+    data: &[Vec<Option<u8>>],
+    config: TrailConfig,
+    head: (usize, usize),
+) -> Vec<Vec<(usize, usize)>> {
+    let mut trails = Vec::new();
+    if data[head.0][head.1] == Some(config.start_height) {
+        extend_trail(data, config, &mut vec![head], &mut trails);
     }
-    println!("Number of distinct routes: {}", sum_of_routes);
-    trail_scores
+    trails
+}
+
+fn extend_trail(
+    data: &[Vec<Option<u8>>],
+    config: TrailConfig,
+    trail: &mut Vec<(usize, usize)>,
+    trails: &mut Vec<Vec<(usize, usize)>>,
+) {
+    let (i, j) = *trail.last().unwrap();
+    let Some(current_height) = data[i][j] else {
+        return;
+    };
+    if current_height == config.summit_height {
+        trails.push(trail.clone());
+        return;
+    }
+    for (x, y) in neighbors(data, i, j) {
+        if data[x][y] == Some(current_height + 1) {
+            trail.push((x, y));
+            extend_trail(data, config, trail, trails);
+            trail.pop();
+        }
+    }
+}
+
+fn parse_coordinate(arg: &str) -> (usize, usize) {
+    let (i, j) = arg
+        .split_once(',')
+        .expect("Trailhead must be given as row,column");
+    (
+        i.trim().parse().expect("Invalid trailhead row"),
+        j.trim().parse().expect("Invalid trailhead column"),
+    )
 }
 
 fn main() {
     let map_data = parse_puzzle_input();
-    let heads = find_trail_heads(&map_data);
-    let scores = calculate_trail_scores(&map_data, &heads);
 
-    let mut sum_of_scores = 0;
-    
-    for (_key, value) in &scores {
-        sum_of_scores += value;
+    // Optional arguments: --start <height> --summit <height> --trails <row,column>
+    let mut config = TrailConfig {
+        start_height: 0,
+        summit_height: 9,
+    };
+    let mut listed_head = None;
+    let args: Vec<String> = env::args().skip(1).collect();
+    for pair in args.chunks(2) {
+        let value = pair
+            .get(1)
+            .unwrap_or_else(|| panic!("Missing value for {}", pair[0]));
+        match pair[0].as_str() {
+            "--start" => config.start_height = value.parse().expect("Invalid start height"),
+            "--summit" => config.summit_height = value.parse().expect("Invalid summit height"),
+            "--trails" => listed_head = Some(parse_coordinate(value)),
+            other => panic!("Unknown option {}", other),
+        }
+    }
+    assert!(
+        config.start_height <= config.summit_height && config.summit_height <= 9,
+        "Heights must satisfy start <= summit <= 9"
+    );
+    if let Some((i, j)) = listed_head {
+        assert!(
+            i < map_data.len() && j < map_data[i].len(),
+            "Trailhead {},{} is off the map ({} rows, {} columns)",
+            i,
+            j,
+            map_data.len(),
+            map_data[0].len()
+        );
     }
+
+    let heads = find_trail_heads(&map_data, config);
+    let scores = calculate_trail_scores(&map_data, config);
+
+    let sum_of_scores: usize = heads.iter().map(|&(i, j)| scores[i][j].score).sum();
+    let sum_of_ratings: u64 = heads.iter().map(|&(i, j)| scores[i][j].rating).sum();
+
+    println!("Number of distinct routes: {}", sum_of_ratings);
     println!("Sum of trail scores: {}", sum_of_scores);
+
+    if let Some(head) = listed_head {
+        let trails = list_trails(&map_data, config, head);
+        println!("Trails from {:?}: {}", head, trails.len());
+        for trail in trails {
+            let steps: Vec<String> = trail.iter().map(|c| format!("{:?}", c)).collect();
+            println!("  {}", steps.join(" -> "));
+        }
+    }
 }
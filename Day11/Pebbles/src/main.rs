@@ -1,15 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
 
-fn parse_puzzle_input() -> HashMap<usize, usize> {
+fn parse_puzzle_input() -> HashMap<u128, u128> {
     let input = fs::read_to_string("puzzle_input.txt").expect("No stones to be seen.");
     let mut stones = HashMap::new();
 
-    let data: Vec<usize> = input
+    let data: Vec<u128> = input
         .lines()
         .flat_map(|line| {
             line.split_whitespace()
-                .filter_map(|c| c.parse::<usize>().ok())
+                .filter_map(|c| c.parse::<u128>().ok())
         })
         .collect();
 
@@ -19,71 +20,594 @@ fn parse_puzzle_input() -> HashMap<usize, usize> {
     stones
 }
 
-fn to_digits(n: usize) -> Vec<usize> {
-    fn x_inner(n: usize, xs: &mut Vec<usize>) {
-        if n >= 10 {
-            x_inner(n / 10, xs);
+fn count_digits(mut n: u128) -> u32 {
+    let mut digits = 1;
+    while n >= 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+fn split_digits(x: u128) -> (u128, u128) {
+    let half = 10_u128.pow(count_digits(x) / 2);
+    (x / half, x % half)
+}
+
+// The puzzle's own rules, written in the rule format below
+const DEFAULT_RULES: &str = "
+value == 0 -> map 1
+digits even -> split
+always -> multiply 2024
+";
+
+// One rule per line: `<condition> [and <condition>...] -> <action>`.
+// The first rule whose conditions all hold is applied, a stone without a
+// matching rule stays as it is. `#` starts a comment.
+//   conditions: always | value|digits <op> N | value|digits even|odd
+//               (op is one of == != < > <= >=)
+//   actions:    map N | add N | multiply N | split
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Value,
+    Digits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    Always,
+    Compare(Quantity, Comparison, u128),
+    Even(Quantity),
+    Odd(Quantity),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Map(u128),
+    Add(u128),
+    Multiply(u128),
+    // Cuts the digits in half, the left stone keeps the extra digit if odd
+    Split,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    conditions: Vec<Condition>,
+    action: Action,
+}
+
+impl Quantity {
+    fn of(self, val: u128) -> u128 {
+        match self {
+            Quantity::Value => val,
+            Quantity::Digits => count_digits(val) as u128,
+        }
+    }
+}
+
+impl Condition {
+    fn holds(self, val: u128) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Compare(quantity, comparison, n) => {
+                let x = quantity.of(val);
+                match comparison {
+                    Comparison::Eq => x == n,
+                    Comparison::Ne => x != n,
+                    Comparison::Lt => x < n,
+                    Comparison::Gt => x > n,
+                    Comparison::Le => x <= n,
+                    Comparison::Ge => x >= n,
+                }
+            }
+            Condition::Even(quantity) => quantity.of(val).is_multiple_of(2),
+            Condition::Odd(quantity) => !quantity.of(val).is_multiple_of(2),
+        }
+    }
+}
+
+impl Action {
+    fn apply(self, val: u128) -> Result<Vec<u128>, String> {
+        let overflow =
+            |op: &str, n: u128| format!("stone {} {} {} does not fit in a u128", val, op, n);
+        match self {
+            Action::Map(n) => Ok(vec![n]),
+            Action::Add(n) => Ok(vec![val.checked_add(n).ok_or_else(|| overflow("+", n))?]),
+            Action::Multiply(n) => Ok(vec![val.checked_mul(n).ok_or_else(|| overflow("*", n))?]),
+            Action::Split => {
+                let (left, right) = split_digits(val);
+                Ok(vec![left, right])
+            }
         }
-        xs.push(n % 10);
     }
-    let mut xs = Vec::new();
-    x_inner(n, &mut xs);
-    xs
 }
 
-fn digits_to_value(digits: &[usize]) -> usize {
-    let length = digits.len();
-    let mut val: usize = 0;
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let quantity = |word: &str| match word {
+        "value" => Ok(Quantity::Value),
+        "digits" => Ok(Quantity::Digits),
+        _ => Err(format!("unknown quantity {:?}", word)),
+    };
 
-    for i in 0..length {
-        val += digits[i] * (10_usize.pow((length - (i + 1)).try_into().unwrap()));
+    match words[..] {
+        ["always"] => Ok(Condition::Always),
+        [q, "even"] => Ok(Condition::Even(quantity(q)?)),
+        [q, "odd"] => Ok(Condition::Odd(quantity(q)?)),
+        [q, op, n] => {
+            let comparison = match op {
+                "==" => Comparison::Eq,
+                "!=" => Comparison::Ne,
+                "<" => Comparison::Lt,
+                ">" => Comparison::Gt,
+                "<=" => Comparison::Le,
+                ">=" => Comparison::Ge,
+                _ => return Err(format!("unknown comparison {:?}", op)),
+            };
+            let n = n.parse().map_err(|_| format!("invalid number {:?}", n))?;
+            Ok(Condition::Compare(quantity(q)?, comparison, n))
+        }
+        _ => Err(format!("cannot read condition {:?}", text.trim())),
     }
-    val
 }
 
-fn split_digits(x: usize) -> Option<(usize, usize)> {
-    let digits = to_digits(x);
+fn parse_action(text: &str) -> Result<Action, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let number = |n: &str| n.parse().map_err(|_| format!("invalid number {:?}", n));
 
-    if !(digits.len() % 2 == 0) {
-        return None;
+    match words[..] {
+        ["split"] => Ok(Action::Split),
+        ["map", n] => Ok(Action::Map(number(n)?)),
+        ["add", n] => Ok(Action::Add(number(n)?)),
+        ["multiply", n] => Ok(Action::Multiply(number(n)?)),
+        _ => Err(format!("cannot read action {:?}", text.trim())),
     }
-    let (left, right) = digits.split_at(digits.len() / 2);
-    Some((digits_to_value(left), digits_to_value(right)))
 }
 
-fn update_stones(stones: HashMap<usize, usize>) -> HashMap<usize, usize> {
+fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    let mut rules = Vec::new();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |err: String| format!("line {}: {}", line_idx + 1, err);
+        let (conditions, action) = line
+            .split_once("->")
+            .ok_or_else(|| error("expected `<condition> -> <action>`".to_string()))?;
+
+        rules.push(Rule {
+            conditions: conditions
+                .split(" and ")
+                .map(parse_condition)
+                .collect::<Result<_, _>>()
+                .map_err(error)?,
+            action: parse_action(action).map_err(error)?,
+        });
+    }
+    Ok(rules)
+}
+
+fn add_stones(stones: &mut HashMap<u128, u128>, val: u128, count: u128) -> Result<(), String> {
+    let entry = stones.entry(val).or_insert(0);
+    *entry = entry
+        .checked_add(count)
+        .ok_or_else(|| format!("more than {} stones with value {}", u128::MAX, val))?;
+    Ok(())
+}
+
+fn update_stones(
+    stones: HashMap<u128, u128>,
+    rules: &[Rule],
+) -> Result<HashMap<u128, u128>, String> {
     let mut new_stones = HashMap::with_capacity(stones.len());
 
     for (val, count) in stones {
-        if val == 0 {
-            *new_stones.entry(1).or_insert(0) += count;
-        } else if let Some((left, right)) = split_digits(val) {
-            *new_stones.entry(left).or_insert(0) += count;
-            *new_stones.entry(right).or_insert(0) += count;
+        for new_val in transform(val, rules)? {
+            add_stones(&mut new_stones, new_val, count)?;
+        }
+    }
+    Ok(new_stones)
+}
+
+// The stones a single stone turns into after one blink
+fn transform(val: u128, rules: &[Rule]) -> Result<Vec<u128>, String> {
+    let rule = rules
+        .iter()
+        .find(|rule| rule.conditions.iter().all(|c| c.holds(val)));
+    match rule {
+        Some(rule) => rule.action.apply(val),
+        None => Ok(vec![val]),
+    }
+}
+
+fn count_stones(stones: &HashMap<u128, u128>) -> Option<u128> {
+    stones
+        .values()
+        .try_fold(0_u128, |total, &count| total.checked_add(count))
+}
+
+// More reachable values than this are treated as unbounded
+const REACHABLE_LIMIT: usize = 1_000_000;
+// Extrapolation costs O(n^2) per step in the number of reachable values
+const EXTRAPOLATION_LIMIT: usize = 5_000;
+// Counts for far-away blinks are only reported modulo this prime
+const MODULUS: u64 = 1_000_000_007;
+
+struct BlinkStats {
+    blink: u32,
+    total: Option<u128>,
+    distinct: usize,
+    largest: u128,
+}
+
+fn blink_stats(blink: u32, stones: &HashMap<u128, u128>) -> BlinkStats {
+    BlinkStats {
+        blink,
+        total: count_stones(stones),
+        distinct: stones.len(),
+        largest: stones.keys().copied().max().unwrap_or(0),
+    }
+}
+
+fn print_population(history: &[BlinkStats]) {
+    println!(
+        "{:>6} {:>40} {:>10} {:>16} {:>8}",
+        "Blink", "Stones", "Distinct", "Largest", "Growth"
+    );
+    for (i, stats) in history.iter().enumerate() {
+        let total = stats
+            .total
+            .map_or("overflow".to_string(), |t| t.to_string());
+        let growth = match (i.checked_sub(1).and_then(|p| history[p].total), stats.total) {
+            (Some(previous), Some(total)) if previous > 0 => {
+                format!("{:.4}", total as f64 / previous as f64)
+            }
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:>6} {:>40} {:>10} {:>16} {:>8}",
+            stats.blink, total, stats.distinct, stats.largest, growth
+        );
+    }
+}
+
+// Every value the stones can ever take, starting values included.
+// None if it looks unbounded.
+fn find_reachable(
+    stones: &HashMap<u128, u128>,
+    rules: &[Rule],
+) -> Result<Option<Vec<u128>>, String> {
+    let mut reachable: HashSet<u128> = stones.keys().copied().collect();
+    let mut queue: Vec<u128> = reachable.iter().copied().collect();
+
+    while let Some(val) = queue.pop() {
+        for new_val in transform(val, rules)? {
+            if reachable.insert(new_val) {
+                if reachable.len() > REACHABLE_LIMIT {
+                    return Ok(None);
+                }
+                queue.push(new_val);
+            }
+        }
+    }
+    let mut reachable: Vec<u128> = reachable.into_iter().collect();
+    reachable.sort();
+    Ok(Some(reachable))
+}
+
+fn successor_indices(values: &[u128], rules: &[Rule]) -> Result<Vec<Vec<usize>>, String> {
+    let index: HashMap<u128, usize> = values.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    values
+        .iter()
+        .map(|&val| Ok(transform(val, rules)?.iter().map(|v| index[v]).collect()))
+        .collect()
+}
+
+// The values the stones eventually cycle through: those that can turn back
+// into themselves. Kosaraju's algorithm, with explicit stacks since the
+// graph can be far too deep for recursion.
+fn find_recurrent(reachable: &[u128], rules: &[Rule]) -> Result<Vec<u128>, String> {
+    let successors = successor_indices(reachable, rules)?;
+    let n = reachable.len();
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (from, targets) in successors.iter().enumerate() {
+        for &to in targets {
+            predecessors[to].push(from);
+        }
+    }
+
+    // Finishing order of a depth-first search over the successors
+    let mut visited = vec![false; n];
+    let mut finished = Vec::with_capacity(n);
+    for root in 0..n {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            if let Some(&child) = successors[*node].get(*next) {
+                *next += 1;
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push((child, 0));
+                }
+            } else {
+                finished.push(*node);
+                stack.pop();
+            }
+        }
+    }
+
+    // Strongly connected components, from the predecessors in reverse order
+    let mut component = vec![usize::MAX; n];
+    let mut sizes = Vec::new();
+    for &root in finished.iter().rev() {
+        if component[root] != usize::MAX {
+            continue;
+        }
+        let id = sizes.len();
+        let mut size = 0;
+        component[root] = id;
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            size += 1;
+            for &previous in &predecessors[node] {
+                if component[previous] == usize::MAX {
+                    component[previous] = id;
+                    stack.push(previous);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+
+    Ok((0..n)
+        .filter(|&i| sizes[component[i]] > 1 || successors[i].contains(&i))
+        .map(|i| reachable[i])
+        .collect())
+}
+
+// Stone count after `blinks` blinks, modulo MODULUS. The count is v * M^k * 1
+// for the transition matrix M over the reachable values. Instead of squaring M itself
+// (n^3 per product for thousands of values), Berlekamp-Massey finds the
+// minimal recurrence of the counts from their first 2n terms, and M^k is
+// evaluated as x^k modulo that recurrence's polynomial (Cayley-Hamilton).
+fn extrapolate_count(
+    stones: &HashMap<u128, u128>,
+    rules: &[Rule],
+    reachable: &[u128],
+    blinks: u64,
+) -> Result<u64, String> {
+    if reachable.len() > EXTRAPOLATION_LIMIT {
+        return Err(format!(
+            "{} reachable values, extrapolation is limited to {}",
+            reachable.len(),
+            EXTRAPOLATION_LIMIT
+        ));
+    }
+    let index: HashMap<u128, usize> = reachable.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+    let successors = successor_indices(reachable, rules)?;
+
+    let mut counts = vec![0_u64; reachable.len()];
+    for (val, &count) in stones {
+        counts[index[val]] = (count % MODULUS as u128) as u64;
+    }
+
+    let terms = (2 * reachable.len() as u64 + 2).min(blinks + 1);
+    let mut sequence: Vec<u64> = Vec::with_capacity(terms as usize);
+    for _ in 0..terms {
+        sequence.push(counts.iter().fold(0, |total, &c| (total + c) % MODULUS));
+
+        let mut next = vec![0_u64; reachable.len()];
+        for (from, &count) in counts.iter().enumerate() {
+            for &to in &successors[from] {
+                next[to] = (next[to] + count) % MODULUS;
+            }
+        }
+        counts = next;
+    }
+    if blinks < terms {
+        return Ok(sequence[blinks as usize]);
+    }
+
+    let recurrence = berlekamp_massey(&sequence);
+    Ok(recurrence_term(&recurrence, &sequence, blinks))
+}
+
+fn mod_pow(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1;
+    base %= MODULUS;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % MODULUS;
+        }
+        base = base * base % MODULUS;
+        exponent >>= 1;
+    }
+    result
+}
+
+// Coefficients c with a[t] = c[0] * a[t-1] + c[1] * a[t-2] + ...
+fn berlekamp_massey(sequence: &[u64]) -> Vec<u64> {
+    let mut current: Vec<u64> = vec![1];
+    let mut previous: Vec<u64> = vec![1];
+    let mut length = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = 1;
+
+    for i in 0..sequence.len() {
+        let discrepancy = current[1..=length]
+            .iter()
+            .zip(sequence[..i].iter().rev())
+            .fold(sequence[i], |d, (&c, &a)| (d + c * a) % MODULUS);
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let factor = discrepancy * mod_pow(previous_discrepancy, MODULUS - 2) % MODULUS;
+        let saved = current.clone();
+        if current.len() < previous.len() + shift {
+            current.resize(previous.len() + shift, 0);
+        }
+        for (j, &p) in previous.iter().enumerate() {
+            current[j + shift] = (current[j + shift] + MODULUS - factor * p % MODULUS) % MODULUS;
+        }
+
+        if 2 * length <= i {
+            length = i + 1 - length;
+            previous = saved;
+            previous_discrepancy = discrepancy;
+            shift = 1;
         } else {
-            *new_stones.entry(val * 2024).or_insert(0) += count;
+            shift += 1;
         }
     }
-    new_stones
+    current.resize(length + 1, 0);
+    current[1..]
+        .iter()
+        .map(|&c| (MODULUS - c) % MODULUS)
+        .collect()
 }
 
-fn count_stones(stones: &HashMap<usize, usize>) -> usize {
-    stones.values().sum()
+// Product of two polynomials, reduced with x^L = c[0] x^(L-1) + ... + c[L-1]
+fn multiply_mod(a: &[u64], b: &[u64], recurrence: &[u64]) -> Vec<u64> {
+    let order = recurrence.len();
+    let mut product = vec![0_u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            product[i + j] = (product[i + j] + x * y) % MODULUS;
+        }
+    }
+    for degree in (order..product.len()).rev() {
+        let coefficient = product[degree];
+        if coefficient == 0 {
+            continue;
+        }
+        for (i, &c) in recurrence.iter().enumerate() {
+            let target = degree - 1 - i;
+            product[target] = (product[target] + coefficient * c) % MODULUS;
+        }
+    }
+    product.truncate(order);
+    product
+}
+
+fn recurrence_term(recurrence: &[u64], sequence: &[u64], n: u64) -> u64 {
+    if recurrence.is_empty() {
+        return 0;
+    }
+    let mut result = multiply_mod(&[1], &[1], recurrence);
+    let mut base = multiply_mod(&[0, 1], &[1], recurrence);
+    let mut exponent = n;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = multiply_mod(&result, &base, recurrence);
+        }
+        base = multiply_mod(&base, &base, recurrence);
+        exponent >>= 1;
+    }
+    result
+        .iter()
+        .zip(sequence)
+        .fold(0, |total, (&r, &a)| (total + r * a) % MODULUS)
 }
 
 fn main() {
-    let blinks = 75;
-    let mut stones = parse_puzzle_input();
+    // Optional arguments: number of blinks (defaults to 75), a rule file
+    // ("-" for the default rules) and a far-away blink number to extrapolate to
+    let args: Vec<String> = env::args().skip(1).collect();
+    let blinks: u32 = args
+        .first()
+        .map(|arg| arg.parse().expect("Blink count must be a number"))
+        .unwrap_or(75);
+    let rule_text = match args.get(1).filter(|path| *path != "-") {
+        Some(path) => fs::read_to_string(path).expect("Could not read the rule file"),
+        None => DEFAULT_RULES.to_string(),
+    };
+    let rules = parse_rules(&rule_text).unwrap_or_else(|err| panic!("Invalid rules: {}", err));
+    let far_blinks: Option<u64> = args
+        .get(2)
+        .map(|arg| arg.parse().expect("Far blink count must be a number"));
+    let initial_stones = parse_puzzle_input();
+    let mut stones = initial_stones.clone();
+    let mut history = vec![blink_stats(0, &stones)];
 
     for i in 1..blinks + 1 {
-        stones = update_stones(stones);
+        stones = match update_stones(stones, &rules) {
+            Ok(new_stones) => new_stones,
+            Err(err) => {
+                println!("Overflow during blink {}: {}", i, err);
+                break;
+            }
+        };
+        history.push(blink_stats(i, &stones));
 
-        if i % 25 == 0 {
+        if i % 25 == 0 || i == blinks {
+            match count_stones(&stones) {
+                Some(total) => println!(
+                    "After {} blinks, there are {} stones in the array.",
+                    i, total
+                ),
+                None => println!(
+                    "After {} blinks, there are more than {} stones in the array.",
+                    i,
+                    u128::MAX
+                ),
+            }
+        }
+    }
+    println!();
+    print_population(&history);
+
+    let reachable = match find_reachable(&initial_stones, &rules) {
+        Ok(Some(reachable)) => reachable,
+        Ok(None) => {
             println!(
-                "After {} blinks, there are {} stones in the array.",
-                i,
-                count_stones(&stones)
+                "The stone values keep growing past {} distinct values.",
+                REACHABLE_LIMIT
             );
+            return;
+        }
+        Err(err) => {
+            println!("Could not explore the stone values: {}", err);
+            return;
+        }
+    };
+    println!(
+        "The stones can reach {} distinct values, the largest being {}.",
+        reachable.len(),
+        reachable.last().unwrap_or(&0)
+    );
+    match find_recurrent(&reachable, &rules) {
+        Ok(recurrent) => println!(
+            "They eventually cycle through {} of them, the largest being {}.",
+            recurrent.len(),
+            recurrent.last().unwrap_or(&0)
+        ),
+        Err(err) => println!("Could not find the recurring values: {}", err),
+    }
+
+    if let Some(far_blinks) = far_blinks {
+        match extrapolate_count(&initial_stones, &rules, &reachable, far_blinks) {
+            Ok(count) => println!(
+                "After {} blinks, there are {} stones modulo {}.",
+                far_blinks, count, MODULUS
+            ),
+            Err(err) => println!("Could not extrapolate: {}", err),
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::fs;
 
 fn parse_puzzle_input() -> Vec<Vec<char>> {
@@ -12,8 +12,22 @@ fn parse_puzzle_input() -> Vec<Vec<char>> {
     data
 }
 
+#[derive(Debug, Clone)]
+struct Region {
+    id: usize,
+    plant: char,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    // (top row, left column, bottom row, right column), inclusive
+    bounds: (usize, usize, usize, usize),
+    cells: Vec<(usize, usize)>,
+    // The innermost region that cuts this one off from the edge of the map
+    enclosed_by: Option<usize>,
+}
+
 fn fetch_neighbor(
-    map_data: &Vec<Vec<char>>,
+    map_data: &[Vec<char>],
     i: usize,
     j: usize,
     di: isize,
@@ -25,7 +39,7 @@ fn fetch_neighbor(
     map_data.get(ni)?.get(nj).copied()
 }
 
-fn count_corners(map_data: &Vec<Vec<char>>, i: usize, j: usize) -> usize {
+fn count_corners(map_data: &[Vec<char>], i: usize, j: usize) -> usize {
     let mut corners = 0;
 
     let corner_patterns = [
@@ -38,79 +52,413 @@ fn count_corners(map_data: &Vec<Vec<char>>, i: usize, j: usize) -> usize {
     let current = map_data[i][j];
     // There are 4 corner cases with three possible subcases (outer corner, inner corner, no corner)
     for (horizontal, vertical, diagonal) in corner_patterns {
-        let n1 = fetch_neighbor(&map_data, i, j, horizontal.0, horizontal.1);
-        let n2 = fetch_neighbor(&map_data, i, j, vertical.0, vertical.1);
-        let d = fetch_neighbor(&map_data, i, j, diagonal.0, diagonal.1);
+        let n1 = fetch_neighbor(map_data, i, j, horizontal.0, horizontal.1);
+        let n2 = fetch_neighbor(map_data, i, j, vertical.0, vertical.1);
+        let d = fetch_neighbor(map_data, i, j, diagonal.0, diagonal.1);
 
-        if n1 != Some(current) && n2 != Some(current) {
-            corners += 1;
-        } else if n1 == Some(current) && n2 == Some(current) && d != Some(current) {
+        let outer = n1 != Some(current) && n2 != Some(current);
+        let inner = n1 == Some(current) && n2 == Some(current) && d != Some(current);
+        if outer || inner {
             corners += 1;
-        } else {
-            // No corner
         }
     }
     corners
 }
 
-fn calculate_perimeter(map_data: &Vec<Vec<char>>) -> (usize, usize) {
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+
+// Disjoint sets over the cells, merged by size with path halving
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(count: usize) -> Self {
+        UnionFind {
+            parent: (0..count).collect(),
+            size: vec![1; count],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+// Region ids for every cell, numbered in the order their first cell appears
+fn label_regions(map_data: &[Vec<char>]) -> (Vec<Vec<usize>>, usize) {
     let rows = map_data.len();
     let cols = map_data[0].len();
-    let mut visited = HashSet::new();
-    let mut total_price = 0;
-    let mut reduced_price = 0;
+    let mut sets = UnionFind::new(rows * cols);
+
+    for i in 0..rows {
+        for j in 0..cols {
+            if i + 1 < rows && map_data[i + 1][j] == map_data[i][j] {
+                sets.union(i * cols + j, (i + 1) * cols + j);
+            }
+            if j + 1 < cols && map_data[i][j + 1] == map_data[i][j] {
+                sets.union(i * cols + j, i * cols + j + 1);
+            }
+        }
+    }
+
+    let mut ids = HashMap::new();
+    let mut labels = vec![vec![0; cols]; rows];
+    for (i, label_row) in labels.iter_mut().enumerate() {
+        for (j, label) in label_row.iter_mut().enumerate() {
+            let root = sets.find(i * cols + j);
+            let next_id = ids.len();
+            *label = *ids.entry(root).or_insert(next_id);
+        }
+    }
+    (labels, ids.len())
+}
+
+fn find_regions(map_data: &[Vec<char>]) -> (Vec<Region>, Vec<Vec<usize>>) {
+    let (labels, region_count) = label_regions(map_data);
+    let mut regions: Vec<Option<Region>> = vec![None; region_count];
 
-    let directions = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+    for (i, label_row) in labels.iter().enumerate() {
+        for (j, &id) in label_row.iter().enumerate() {
+            let region = regions[id].get_or_insert_with(|| Region {
+                id,
+                plant: map_data[i][j],
+                area: 0,
+                perimeter: 0,
+                sides: 0,
+                bounds: (i, j, i, j),
+                cells: Vec::new(),
+                enclosed_by: None,
+            });
+            region.area += 1;
+            region.sides += count_corners(map_data, i, j);
+            region.cells.push((i, j));
+            // Every side not shared with the same region needs a fence
+            region.perimeter += DIRECTIONS
+                .iter()
+                .filter(|&&(di, dj)| label_at(&labels, i, j, di, dj) != Some(id))
+                .count();
+
+            let (top, left, bottom, right) = &mut region.bounds;
+            *top = (*top).min(i);
+            *left = (*left).min(j);
+            *bottom = (*bottom).max(i);
+            *right = (*right).max(j);
+        }
+    }
+
+    let mut regions: Vec<Region> = regions.into_iter().flatten().collect();
+    mark_enclosures(&mut regions, &labels);
+    (regions, labels)
+}
+
+fn label_at(labels: &[Vec<usize>], i: usize, j: usize, di: isize, dj: isize) -> Option<usize> {
+    let ni = i.checked_add_signed(di)?;
+    let nj = j.checked_add_signed(dj)?;
+
+    labels.get(ni)?.get(nj).copied()
+}
+
+// The regions that can't reach the edge of the map without crossing `wall`
+fn regions_inside(wall: usize, labels: &[Vec<usize>], region_count: usize) -> Vec<usize> {
+    let rows = labels.len();
+    let cols = labels[0].len();
+    let mut reached = vec![vec![false; cols]; rows];
+    let mut stack = Vec::new();
 
     for i in 0..rows {
         for j in 0..cols {
-            if visited.contains(&(i, j)) {
+            let on_edge = i == 0 || j == 0 || i == rows - 1 || j == cols - 1;
+            if on_edge && labels[i][j] != wall {
+                reached[i][j] = true;
+                stack.push((i, j));
+            }
+        }
+    }
+    while let Some((i, j)) = stack.pop() {
+        for (di, dj) in DIRECTIONS {
+            let (Some(ni), Some(nj)) = (i.checked_add_signed(di), j.checked_add_signed(dj)) else {
                 continue;
+            };
+            if ni < rows && nj < cols && !reached[ni][nj] && labels[ni][nj] != wall {
+                reached[ni][nj] = true;
+                stack.push((ni, nj));
             }
-            // Start of a new region
-            let plot_char = map_data[i][j];
-            let mut area = 0;
-            let mut corners = 0;
-            let mut perimeter = 0;
-            let mut stack = vec![(i, j)];
-            visited.insert((i, j));
-
-            while let Some((curr_i, curr_j)) = stack.pop() {
-                area += 1;
-                corners += count_corners(&map_data, curr_i, curr_j);
-
-                // Calculate the absolute perimeter "sides" for this tile
-                for (di, dj) in directions {
-                    let ni = curr_i.checked_add_signed(di);
-                    let nj = curr_j.checked_add_signed(dj);
-
-                    let neighbor = ni
-                        .and_then(|r| map_data.get(r))
-                        .and_then(|row| nj.and_then(|c| row.get(c)));
-
-                    if neighbor == Some(&plot_char) {
-                        let ni = ni.unwrap();
-                        let nj = nj.unwrap();
-                        if !visited.contains(&(ni, nj)) {
-                            visited.insert((ni, nj));
-                            stack.push((ni, nj));
-                        }
-                    } else {
-                        perimeter += 1;
+        }
+    }
+
+    let mut outside = vec![false; region_count];
+    outside[wall] = true;
+    for (label_row, reached_row) in labels.iter().zip(&reached) {
+        for (&label, &was_reached) in label_row.iter().zip(reached_row) {
+            if was_reached {
+                outside[label] = true;
+            }
+        }
+    }
+    (0..region_count).filter(|&id| !outside[id]).collect()
+}
+
+fn mark_enclosures(regions: &mut [Region], labels: &[Vec<usize>]) {
+    let rows = labels.len();
+    let cols = labels[0].len();
+    // Only a region that touches an inner region can cut it off
+    let inner: Vec<bool> = regions
+        .iter()
+        .map(|r| r.bounds.0 > 0 && r.bounds.1 > 0 && r.bounds.2 < rows - 1 && r.bounds.3 < cols - 1)
+        .collect();
+    let mut enclosers: Vec<Vec<usize>> = vec![Vec::new(); regions.len()];
+
+    for region in regions.iter() {
+        let borders_inner = region.cells.iter().any(|&(i, j)| {
+            DIRECTIONS.iter().any(|&(di, dj)| {
+                let ni = i.checked_add_signed(di).filter(|&r| r < rows);
+                let nj = j.checked_add_signed(dj).filter(|&c| c < cols);
+                matches!((ni, nj), (Some(ni), Some(nj)) if inner[labels[ni][nj]] && labels[ni][nj] != region.id)
+            })
+        });
+        if !borders_inner {
+            continue;
+        }
+        for id in regions_inside(region.id, labels, regions.len()) {
+            enclosers[id].push(region.id);
+        }
+    }
+
+    // Nested walls all enclose the innermost regions, the closest one is
+    // itself enclosed by every other
+    for region in regions.iter_mut() {
+        region.enclosed_by = enclosers[region.id]
+            .iter()
+            .copied()
+            .max_by_key(|&wall| enclosers[wall].len());
+    }
+}
+
+fn print_region_table(regions: &[Region]) {
+    println!(
+        "{:>5} {:>5} {:>6} {:>9} {:>6} {:>10} {:>10} {:>18} {:>9}",
+        "Id", "Plant", "Area", "Perimeter", "Sides", "Price", "Bulk", "Bounds", "Enclosed"
+    );
+    for region in regions {
+        let (top, left, bottom, right) = region.bounds;
+        println!(
+            "{:>5} {:>5} {:>6} {:>9} {:>6} {:>10} {:>10} {:>18} {:>9}",
+            region.id,
+            region.plant,
+            region.area,
+            region.perimeter,
+            region.sides,
+            region.area * region.perimeter,
+            region.area * region.sides,
+            format!("({},{})-({},{})", top, left, bottom, right),
+            region
+                .enclosed_by
+                .map_or("-".to_string(), |id| id.to_string())
+        );
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '"' => vec!['\\', '"'],
+            '\\' => vec!['\\', '\\'],
+            c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+fn regions_to_json(regions: &[Region]) -> String {
+    let entries: Vec<String> = regions
+        .iter()
+        .map(|region| {
+            let (top, left, bottom, right) = region.bounds;
+            let cells: Vec<String> = region
+                .cells
+                .iter()
+                .map(|(r, c)| format!("[{},{}]", r, c))
+                .collect();
+            format!(
+                "  {{\"id\":{},\"plant\":\"{}\",\"area\":{},\"perimeter\":{},\"sides\":{},\
+                 \"price\":{},\"bulk_price\":{},\
+                 \"bounds\":{{\"top\":{},\"left\":{},\"bottom\":{},\"right\":{}}},\
+                 \"enclosed_by\":{},\"cells\":[{}]}}",
+                region.id,
+                escape_json(&region.plant.to_string()),
+                region.area,
+                region.perimeter,
+                region.sides,
+                region.area * region.perimeter,
+                region.area * region.sides,
+                top,
+                left,
+                bottom,
+                right,
+                region
+                    .enclosed_by
+                    .map_or("null".to_string(), |id| id.to_string()),
+                cells.join(",")
+            )
+        })
+        .collect();
+    format!("[\n{}\n]", entries.join(",\n"))
+}
+
+// Evenly spread hues so that consecutive ids never look alike
+fn region_colour(id: usize) -> (u8, u8, u8) {
+    let hue = (id as f64 * 0.618_033_988_75).fract() * 6.0;
+    let lightness = [0.85, 0.65, 0.75][id % 3];
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |v: f64| ((v * 0.6 + 0.4) * lightness * 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+// Cells are painted with their region's colour; fences are drawn on the grid
+// lines between cells that belong to different regions
+fn print_region_map(map_data: &[Vec<char>], labels: &[Vec<usize>]) {
+    let rows = labels.len();
+    let cols = labels[0].len();
+    let at = |i: isize, j: isize| -> Option<usize> {
+        if i < 0 || j < 0 {
+            return None;
+        }
+        labels.get(i as usize)?.get(j as usize).copied()
+    };
+
+    for i in 0..=rows as isize {
+        // The grid line above row i, walking over the corners between cells
+        let mut line = String::new();
+        for j in 0..=cols as isize {
+            let up = at(i - 1, j - 1) != at(i - 1, j);
+            let down = at(i, j - 1) != at(i, j);
+            let left = at(i - 1, j - 1) != at(i, j - 1);
+            let right = at(i - 1, j) != at(i, j);
+            line.push(match (up || down, left || right) {
+                (true, true) => '+',
+                (true, false) => '|',
+                (false, true) => '-',
+                (false, false) => ' ',
+            });
+            if j < cols as isize {
+                line.push_str(if right { "---" } else { "   " });
+            }
+        }
+        println!("{}", line);
+        if i == rows as isize {
+            break;
+        }
+
+        let mut line = String::new();
+        for j in 0..=cols as isize {
+            line.push(if at(i, j - 1) != at(i, j) { '|' } else { ' ' });
+            if let Some(id) = at(i, j) {
+                let (r, g, b) = region_colour(id);
+                line.push_str(&format!(
+                    "\x1b[48;2;{};{};{}m\x1b[30m {} \x1b[0m",
+                    r, g, b, map_data[i as usize][j as usize]
+                ));
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+const PPM_CELL: usize = 8;
+
+// Binary PPM, PPM_CELL pixels per cell with black fences on region borders
+fn write_region_ppm(path: &str, labels: &[Vec<usize>]) -> std::io::Result<()> {
+    let rows = labels.len();
+    let cols = labels[0].len();
+    let (height, width) = (rows * PPM_CELL + 1, cols * PPM_CELL + 1);
+    let mut pixels = vec![0_u8; width * height * 3];
+
+    for (i, label_row) in labels.iter().enumerate() {
+        for (j, &id) in label_row.iter().enumerate() {
+            let (r, g, b) = region_colour(id);
+            let fence_up = label_at(labels, i, j, -1, 0) != Some(id);
+            let fence_left = label_at(labels, i, j, 0, -1) != Some(id);
+            for y in 0..PPM_CELL {
+                for x in 0..PPM_CELL {
+                    let on_fence =
+                        (y == 0 && fence_up) || (x == 0 && fence_left) || (x == 0 && y == 0);
+                    if !on_fence {
+                        let offset = ((i * PPM_CELL + y) * width + j * PPM_CELL + x) * 3;
+                        pixels[offset..offset + 3].copy_from_slice(&[r, g, b]);
                     }
                 }
             }
-            total_price += area * perimeter;
-            reduced_price += area * corners;
         }
     }
-    (total_price, reduced_price)
+
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    data.extend(pixels);
+    fs::write(path, data)
 }
 
 fn main() {
+    // Optional arguments: "table" (default), "json", "map" or "ppm", and a
+    // file to write the JSON or PPM output to
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let format = args.first().map(String::as_str).unwrap_or("table");
+
     let map_data = parse_puzzle_input();
-    let (total_cost, reduced_cost) = calculate_perimeter(&map_data);
+    let (regions, labels) = find_regions(&map_data);
+    let total_cost: usize = regions.iter().map(|r| r.area * r.perimeter).sum();
+    let reduced_cost: usize = regions.iter().map(|r| r.area * r.sides).sum();
+
+    match format {
+        "table" => print_region_table(&regions),
+        "json" => {
+            let json = regions_to_json(&regions);
+            match args.get(1) {
+                Some(path) => {
+                    fs::write(path, json).expect("Could not write the region catalogue");
+                    println!("Wrote {} regions to {}", regions.len(), path);
+                }
+                None => println!("{}", json),
+            }
+            return;
+        }
+        "map" => print_region_map(&map_data, &labels),
+        "ppm" => {
+            let path = args.get(1).map(String::as_str).unwrap_or("regions.ppm");
+            write_region_ppm(path, &labels).expect("Could not write the region map");
+            println!("Wrote {} regions to {}", regions.len(), path);
+        }
+        other => panic!(
+            "Unknown output format {:?}, expected table, json, map or ppm",
+            other
+        ),
+    }
 
+    println!();
     println!("Total cost: {}", total_cost);
     println!("Reduced cost (using full sides): {}", reduced_cost);
 }
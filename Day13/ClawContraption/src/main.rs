@@ -7,7 +7,6 @@ struct Contraption {
     prize_y: i64,
 }
 
-
 impl Contraption {
     fn print_machine(&self) {
         println!();
@@ -22,100 +21,201 @@ impl Contraption {
         println!("Prize: X={}, Y={}", self.prize_x, self.prize_y);
     }
 
-    fn calculate_minimal_tokens(&self) -> Option<u64> {
-        // In the end it comes down to: 
+    // Ok(None) if the prize can't be won, Err if the numbers get out of range
+    fn calculate_minimal_tokens(&self, part: &Part) -> Result<Option<(u128, u128, u128)>, String> {
+        // In the end it comes down to:
         // X = a*A(x) + b*B(x)
-        // Y = a*A(y) + b*B(y) 
+        // Y = a*A(y) + b*B(y)
         //
         // -> Solved for a, b.
         // ...using Cramer's rule.
+        let (ax, ay) = (
+            self.a_button_movement.0 as i128,
+            self.a_button_movement.1 as i128,
+        );
+        let (bx, by) = (
+            self.b_button_movement.0 as i128,
+            self.b_button_movement.1 as i128,
+        );
+        let prize_x = self.prize_x as i128 + part.prize_offset as i128;
+        let prize_y = self.prize_y as i128 + part.prize_offset as i128;
+        let out_of_range = || "numbers out of range".to_string();
+        // a * b - c * d
+        let cross =
+            |a: i128, b: i128, c: i128, d: i128| a.checked_mul(b)?.checked_sub(c.checked_mul(d)?);
 
-        let determinante = (self.a_button_movement.0 * self.b_button_movement.1) - (self.a_button_movement.1 * self.b_button_movement.0);
+        let determinante = cross(ax, by, ay, bx).ok_or_else(out_of_range)?;
 
         if determinante == 0 {
-            return None;
+            return Ok(None);
         }
-        let a = ((self.prize_x * self.b_button_movement.1) - (self.prize_y * self.b_button_movement.0)) / determinante;
-        let b = ((self.prize_y * self.a_button_movement.0) - (self.prize_x * self.a_button_movement.1)) / determinante;
+        let a = cross(prize_x, by, prize_y, bx).ok_or_else(out_of_range)? / determinante;
+        let b = cross(prize_y, ax, prize_x, ay).ok_or_else(out_of_range)? / determinante;
 
-        // Part I shenanigans, obsolete with part II:
-        // if a > 100 || b > 100 {
-        //     return None;
-        // }
-
-        if (a * self.a_button_movement.0 + b * self.b_button_movement.0 == self.prize_x) && (a * self.a_button_movement.1 + b * self.b_button_movement.1 == self.prize_y) {
-            let tokens = 3*a + b;
-            println!("A: {} B: {} (total {} tokens)", a, b, tokens);
+        if a < 0 || b < 0 {
+            return Ok(None);
+        }
+        if let Some(limit) = part.press_limit
+            && (a > limit as i128 || b > limit as i128)
+        {
+            return Ok(None);
+        }
 
-            return Some(tokens as u64);
+        // a, b >= 0 and the prize fits an i128, so these can only overflow
+        // when they miss the prize anyway
+        let reaches = |a_step: i128, b_step: i128, prize: i128| {
+            a.checked_mul(a_step)
+                .zip(b.checked_mul(b_step))
+                .and_then(|(x, y)| x.checked_add(y))
+                == Some(prize)
+        };
+        if reaches(ax, bx, prize_x) && reaches(ay, by, prize_y) {
+            let (a, b) = (a as u128, b as u128);
+            let tokens = a
+                .checked_mul(3)
+                .and_then(|t| t.checked_add(b))
+                .ok_or_else(out_of_range)?;
+            return Ok(Some((a, b, tokens)));
         }
-        None
-    } 
+        Ok(None)
+    }
 }
 
-fn parse_puzzle_input() -> Vec<Contraption> {
-    let input = fs::read_to_string("puzzle_input.txt").expect("WANTED: A file.");
+struct Part {
+    name: &'static str,
+    prize_offset: i64,
+    press_limit: Option<i64>,
+}
+
+const PARTS: [Part; 2] = [
+    Part {
+        name: "Part I",
+        prize_offset: 0,
+        press_limit: Some(100),
+    },
+    // The prizes turned out to be 10^13 further away, with no press limit
+    Part {
+        name: "Part II",
+        prize_offset: 10000000000000,
+        press_limit: None,
+    },
+];
+
+// Machines are blocks of three lines separated by blank lines:
+//   Button A: X+94, Y+34
+//   Button B: X+22, Y+67
+//   Prize: X=8400, Y=5400
+fn parse_puzzle_input(input: &str) -> Result<Vec<Contraption>, String> {
     let mut machines: Vec<Contraption> = Vec::new();
-    let mut a_button = (0, 0);
-    let mut b_button = (0, 0);
-    let mut prize = (0, 0);
-
-    for line in input.lines() {
-        match line {
-            l if l.starts_with("Button A:") => {
-                a_button = parse_coords(l.strip_prefix("Button A: ").unwrap(), false);
-            }
-            l if l.starts_with("Button B:") => {
-                b_button = parse_coords(l.strip_prefix("Button B: ").unwrap(), false);
-            }
-            l if l.starts_with("Prize:") => {
-                prize = parse_coords(l.strip_prefix("Prize: ").unwrap(), true);
-            }
-            _ => {
-                machines.push(Contraption {
-                    a_button_movement: a_button,
-                    b_button_movement: b_button,
-                    prize_x: prize.0,
-                    prize_y: prize.1,
-                });
-            }
+    let mut block: Vec<(usize, &str)> = Vec::new();
+
+    // A final empty line closes the last block even without a trailing blank line
+    for (number, line) in input.lines().chain([""]).enumerate() {
+        let line = line.trim();
+        if !line.is_empty() {
+            block.push((number + 1, line));
+            continue;
+        }
+        if block.is_empty() {
+            continue;
         }
+        let [
+            (a_number, a_line),
+            (b_number, b_line),
+            (prize_number, prize_line),
+        ] = block[..]
+        else {
+            return Err(format!(
+                "Line {}: expected a machine of 3 lines, found {}",
+                block[0].0,
+                block.len()
+            ));
+        };
+        let a_button = parse_line(a_line, "Button A: ", '+')
+            .map_err(|e| format!("Line {}: {}", a_number, e))?;
+        let b_button = parse_line(b_line, "Button B: ", '+')
+            .map_err(|e| format!("Line {}: {}", b_number, e))?;
+        let prize = parse_line(prize_line, "Prize: ", '=')
+            .map_err(|e| format!("Line {}: {}", prize_number, e))?;
+        machines.push(Contraption {
+            a_button_movement: a_button,
+            b_button_movement: b_button,
+            prize_x: prize.0,
+            prize_y: prize.1,
+        });
+        block.clear();
     }
-    machines
+    Ok(machines)
+}
+
+fn parse_line(line: &str, prefix: &str, separator: char) -> Result<(i64, i64), String> {
+    let coords = line
+        .strip_prefix(prefix)
+        .ok_or_else(|| format!("expected {:?}, found {:?}", prefix.trim(), line))?;
+    parse_coords(coords, separator)
+}
+
+// "X+94, Y-34" for buttons, "X=8400, Y=5400" for prizes
+fn parse_coords(s: &str, separator: char) -> Result<(i64, i64), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected two coordinates in {:?}", s))?;
+    Ok((
+        parse_axis(x.trim(), 'X', separator)?,
+        parse_axis(y.trim(), 'Y', separator)?,
+    ))
 }
 
-fn parse_coords(s: &str, prize: bool) -> (i64, i64) {
-    // This honestly has the potential to break in so many ways, but I do not feel it right now.-..
-
-    let parts: Vec<i64> = s
-        .split(',')
-        .map(|part| {
-            part.chars()
-                .filter(|c| c.is_ascii_digit())
-                .collect::<String>()
-                .parse::<i64>()
-                .unwrap_or(0)
-        })
-        .collect();
-
-    // Add constant 10^13 for part II if this is the Prize coordinates...
-    if prize {
-        let c = 10000000000000;
-        return (parts[0] + c, parts[1] + c);    
-    } else {
-        return (parts[0], parts[1]);
+fn parse_axis(s: &str, axis: char, separator: char) -> Result<i64, String> {
+    let value = s
+        .strip_prefix(axis)
+        .ok_or_else(|| format!("expected {} coordinate, found {:?}", axis, s))?;
+    // Buttons carry their own sign ("X-5"), prizes use "X=-5"
+    let number = match value.strip_prefix(separator) {
+        Some(number) => number,
+        None if separator == '+' && value.starts_with('-') => value,
+        None => return Err(format!("expected {}{} in {:?}", axis, separator, s)),
+    };
+    let digits = number.strip_prefix('-').unwrap_or(number);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("{:?} is not a whole number", number));
     }
+    number
+        .parse::<i64>()
+        .map_err(|e| format!("{:?} is out of range: {}", number, e))
 }
 
 fn main() {
-    let machines = parse_puzzle_input();
-    let mut sum_of_tokens = 0;
+    let input = fs::read_to_string("puzzle_input.txt").expect("WANTED: A file.");
+    let machines = match parse_puzzle_input(&input) {
+        Ok(machines) => machines,
+        Err(err) => {
+            println!("Could not read the machines: {}", err);
+            return;
+        }
+    };
+    let mut sums_of_tokens = [Some(0_u128); PARTS.len()];
 
-    for contraption in machines {
+    for contraption in &machines {
         contraption.print_machine();
-        if let Some(x) = contraption.calculate_minimal_tokens() {
-            sum_of_tokens += x;
+        for (part, sum_of_tokens) in PARTS.iter().zip(sums_of_tokens.iter_mut()) {
+            match contraption.calculate_minimal_tokens(part) {
+                Ok(Some((a, b, tokens))) => {
+                    println!("{}: A: {} B: {} (total {} tokens)", part.name, a, b, tokens);
+                    *sum_of_tokens = sum_of_tokens.and_then(|sum| sum.checked_add(tokens));
+                }
+                Ok(None) => println!("{}: no prize", part.name),
+                Err(err) => {
+                    println!("{}: machine skipped, {}", part.name, err);
+                }
+            }
+        }
+    }
+    println!();
+    for (part, sum_of_tokens) in PARTS.iter().zip(sums_of_tokens) {
+        match sum_of_tokens {
+            Some(sum) => println!("{} total token cost: {}", part.name, sum),
+            None => println!("{} total token cost overflows u128", part.name),
         }
     }
-    println!("Total token cost: {}", sum_of_tokens);
 }
@@ -1,41 +1,174 @@
-use regex::Regex;
-use std::fs;
-
-fn read_input() -> String {
-    let input = fs::read_to_string("puzzle_input.txt")
-        .expect("No file found.")
-        .replace('\n', "");
-    split_by_commands(input)
+use std::fs::File;
+use std::io::{self, Read};
+
+const BUFFER_SIZE: usize = 8192;
+
+const DO: &[u8] = b"do()";
+const DONT: &[u8] = b"don't()";
+
+// Position inside a mul(X,Y) instruction matched so far
+#[derive(Clone, Copy, PartialEq)]
+enum MulState {
+    Start,
+    M,
+    U,
+    L,
+    Open,
+    Lhs,
+    Comma,
+    Rhs,
+}
+
+// Byte-at-a-time scanner, so instructions may cross buffer boundaries.
+// do() and don't() are held back until they either complete (and are
+// dropped) or fail (and are passed on to the mul() matcher).
+struct MulScanner {
+    enabled: bool,
+    pending: [u8; 7],
+    pending_len: usize,
+    state: MulState,
+    lhs: u64,
+    rhs: u64,
+    matches: usize,
+    sum: u64,
+}
+
+impl MulScanner {
+    fn new() -> Self {
+        MulScanner {
+            enabled: true,
+            pending: [0; 7],
+            pending_len: 0,
+            state: MulState::Start,
+            lhs: 0,
+            rhs: 0,
+            matches: 0,
+            sum: 0,
+        }
+    }
+
+    fn feed(&mut self, byte: u8) -> io::Result<()> {
+        if byte == b'\n' {
+            return Ok(());
+        }
+        let len = self.pending_len;
+        if len < DONT.len() {
+            self.pending[len] = byte;
+            let candidate = &self.pending[..=len];
+            if candidate == DO || candidate == DONT {
+                self.enabled = candidate == DO;
+                self.pending_len = 0;
+                return Ok(());
+            }
+            if DO.starts_with(candidate) || DONT.starts_with(candidate) {
+                self.pending_len += 1;
+                return Ok(());
+            }
+        }
+        // A partial command that fails to complete is ordinary text
+        if len > 0 {
+            self.pending_len = 0;
+            if self.enabled {
+                let pending = self.pending;
+                for &b in &pending[..len] {
+                    self.match_mul(b)?;
+                }
+            }
+            return self.feed(byte);
+        }
+        if self.enabled {
+            self.match_mul(byte)?;
+        }
+        Ok(())
+    }
+
+    fn match_mul(&mut self, byte: u8) -> io::Result<()> {
+        self.state = match (self.state, byte) {
+            (MulState::Start, b'm') => MulState::M,
+            (MulState::M, b'u') => MulState::U,
+            (MulState::U, b'l') => MulState::L,
+            (MulState::L, b'(') => {
+                self.lhs = 0;
+                self.rhs = 0;
+                MulState::Open
+            }
+            // An operand too large for u64 can't be part of a valid mul()
+            (MulState::Open | MulState::Lhs, b'0'..=b'9') => match push_digit(self.lhs, byte) {
+                Some(lhs) => {
+                    self.lhs = lhs;
+                    MulState::Lhs
+                }
+                None => MulState::Start,
+            },
+            (MulState::Lhs, b',') => MulState::Comma,
+            (MulState::Comma | MulState::Rhs, b'0'..=b'9') => match push_digit(self.rhs, byte) {
+                Some(rhs) => {
+                    self.rhs = rhs;
+                    MulState::Rhs
+                }
+                None => MulState::Start,
+            },
+            (MulState::Rhs, b')') => {
+                let product = self
+                    .lhs
+                    .checked_mul(self.rhs)
+                    .ok_or_else(|| overflow("mul() product"))?;
+                self.sum = self
+                    .sum
+                    .checked_add(product)
+                    .ok_or_else(|| overflow("Sum of mul() statements"))?;
+                self.matches += 1;
+                MulState::Start
+            }
+            (MulState::Start, _) => MulState::Start,
+            // Mismatch: the byte might still begin a new instruction
+            _ => {
+                self.state = MulState::Start;
+                return self.match_mul(byte);
+            }
+        };
+        Ok(())
+    }
+}
+
+fn push_digit(value: u64, byte: u8) -> Option<u64> {
+    value
+        .checked_mul(10)
+        .and_then(|v| v.checked_add((byte - b'0') as u64))
+}
+
+fn overflow(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} overflows u64", what),
+    )
 }
 
-fn split_by_commands(text: String) -> String {
-    let chunks: Vec<&str> = text.split("do()").collect();
-    chunks
-        .into_iter()
-        .map(|s| s.split("don't()").next().unwrap_or(""))
-        .collect::<String>()
+// Returns the number of enabled mul() statements and the sum of their products
+fn evaluate<R: Read>(mut reader: R) -> io::Result<(usize, u64)> {
+    let mut scanner = MulScanner::new();
+    let mut buffer = [0u8; BUFFER_SIZE];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &byte in &buffer[..read] {
+            scanner.feed(byte)?;
+        }
+    }
+    Ok((scanner.matches, scanner.sum))
 }
 
 fn main() {
     // let example = "'*when(932,461)!:who()(*mul(662,950)mul(878,53){#+{&%}mul(675,225)^how(648,963)&)#how(924,189)[mul(14,114)!mul(528,270)]#from()mul(866,868) what())mul(253,100); )when()@{mul(827,104))% -,'mul(955,284)/";
-    let text = read_input();
-    let re = Regex::new(r"mul\((\d+,\d+)\)").unwrap();
-
-    let mut mult_results: Vec<i32> = Vec::new();
-
-    for caps in re.captures_iter(&text) {
-        // caps[0] is the whole match -
-        // caps[1] is the first capture group -
-        let values: Vec<i32> = caps[1]
-            .split(',')
-            .filter_map(|s| s.parse::<i32>().ok())
-            .collect();
-        mult_results.push(values[0] * values[1]);
-    }
-    if mult_results.len() > 0 {
-        println!(
-            "Sum of mul() statements: {}",
-            mult_results.into_iter().sum::<i32>()
-        );
+    let file = File::open("puzzle_input.txt").expect("No file found.");
+    let (matches, sum) = evaluate(file).expect("Could not evaluate mul() statements");
+
+    if matches > 0 {
+        println!("Sum of mul() statements: {}", sum);
     }
 }
@@ -1,5 +1,136 @@
+use std::collections::HashSet;
 use std::fs;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Up,
+    UpRight,
+}
+
+impl Direction {
+    const ALL: [Direction; 8] = [
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+        Direction::Up,
+        Direction::UpRight,
+    ];
+
+    fn delta(self) -> (isize, isize) {
+        match self {
+            Direction::Right => (0, 1),
+            Direction::DownRight => (1, 1),
+            Direction::Down => (1, 0),
+            Direction::DownLeft => (1, -1),
+            Direction::Left => (0, -1),
+            Direction::UpLeft => (-1, -1),
+            Direction::Up => (-1, 0),
+            Direction::UpRight => (-1, 1),
+        }
+    }
+}
+
+// Quarter turns clockwise, applied after the optional mirroring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Orientation {
+    rotation: u8,
+    reflected: bool,
+}
+
+#[derive(Debug, Clone)]
+struct WordMatch {
+    word: String,
+    start: (usize, usize),
+    direction: Direction,
+    cells: Vec<(usize, usize)>,
+}
+
+type TemplateCell = (isize, isize, char);
+
+// A 2-D template; '.' cells in the source text match anything
+#[derive(Debug, Clone)]
+struct Shape {
+    name: String,
+    cells: Vec<TemplateCell>,
+}
+
+#[derive(Debug, Clone)]
+struct ShapeMatch {
+    name: String,
+    start: (usize, usize),
+    orientation: Orientation,
+    cells: Vec<(usize, usize)>,
+}
+
+impl Shape {
+    fn from_template(name: &str, template: &str) -> Self {
+        let cells = template
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c != '.')
+                    .map(move |(j, c)| (i as isize, j as isize, c))
+            })
+            .collect();
+        Shape {
+            name: name.to_string(),
+            cells,
+        }
+    }
+
+    // All distinct rotations and reflections, each shifted so that its
+    // bounding box starts at (0, 0)
+    fn orientations(&self) -> Vec<(Orientation, Vec<TemplateCell>)> {
+        let mut seen = HashSet::new();
+        let mut variants = Vec::new();
+
+        for reflected in [false, true] {
+            for rotation in 0..4 {
+                let mut cells: Vec<TemplateCell> = self
+                    .cells
+                    .iter()
+                    .map(|&(i, j, c)| {
+                        let (mut i, mut j) = if reflected { (i, -j) } else { (i, j) };
+                        for _ in 0..rotation {
+                            (i, j) = (j, -i);
+                        }
+                        (i, j, c)
+                    })
+                    .collect();
+                let min_i = cells.iter().map(|c| c.0).min().unwrap_or(0);
+                let min_j = cells.iter().map(|c| c.1).min().unwrap_or(0);
+                for cell in cells.iter_mut() {
+                    cell.0 -= min_i;
+                    cell.1 -= min_j;
+                }
+                cells.sort();
+
+                if seen.insert(cells.clone()) {
+                    variants.push((
+                        Orientation {
+                            rotation,
+                            reflected,
+                        },
+                        cells,
+                    ));
+                }
+            }
+        }
+        variants
+    }
+}
+
 fn parse_word_puzzle() -> Vec<Vec<char>> {
     let input = fs::read_to_string("puzzle_input.txt").expect("There is no file, maan.");
     let data: Vec<Vec<char>> = input
@@ -10,100 +141,176 @@ fn parse_word_puzzle() -> Vec<Vec<char>> {
     data
 }
 
-fn walk_search_xmas(data: &Vec<Vec<char>>, i: usize, j: usize, max_i: usize, max_j: usize) -> i32 {
-    let mut local_hits = 0;
-    // right
-    if j + 3 < max_j {
-        if data[i][j + 1] == 'M' && data[i][j + 2] == 'A' && data[i][j + 3] == 'S' {
-            local_hits += 1;
-        }
-    }
-    // down-right
-    if j + 3 < max_j && i + 3 < max_i {
-        if data[i + 1][j + 1] == 'M' && data[i + 2][j + 2] == 'A' && data[i + 3][j + 3] == 'S' {
-            local_hits += 1;
-        }
-    }
-    // down
-    if i + 3 < max_i {
-        if data[i + 1][j] == 'M' && data[i + 2][j] == 'A' && data[i + 3][j] == 'S' {
-            local_hits += 1;
-        }
-    }
-    // down-left
-    if i + 3 < max_i && j >= 3 {
-        if data[i + 1][j - 1] == 'M' && data[i + 2][j - 2] == 'A' && data[i + 3][j - 3] == 'S' {
-            local_hits += 1;
-        }
+fn char_at(data: &[Vec<char>], i: isize, j: isize) -> Option<char> {
+    if i < 0 || j < 0 {
+        return None;
     }
-    // left
-    if j >= 3 {
-        if data[i][j - 1] == 'M' && data[i][j - 2] == 'A' && data[i][j - 3] == 'S' {
-            local_hits += 1;
-        }
-    }
-    // up-left
-    if j >= 3 && i >= 3 {
-        if data[i - 1][j - 1] == 'M' && data[i - 2][j - 2] == 'A' && data[i - 3][j - 3] == 'S' {
-            local_hits += 1;
-        }
-    }
-    // up
-    if i >= 3 {
-        if data[i - 1][j] == 'M' && data[i - 2][j] == 'A' && data[i - 3][j] == 'S' {
-            local_hits += 1;
+    data.get(i as usize)?.get(j as usize).copied()
+}
+
+fn walk_search_word(
+    data: &[Vec<char>],
+    word: &str,
+    i: usize,
+    j: usize,
+    directions: &[Direction],
+) -> Vec<WordMatch> {
+    directions
+        .iter()
+        .filter(|direction| {
+            let (di, dj) = direction.delta();
+            word.chars().enumerate().all(|(step, c)| {
+                let step = step as isize;
+                char_at(data, i as isize + di * step, j as isize + dj * step) == Some(c)
+            })
+        })
+        .map(|&direction| {
+            let (di, dj) = direction.delta();
+            WordMatch {
+                word: word.to_string(),
+                start: (i, j),
+                direction,
+                cells: (0..word.chars().count() as isize)
+                    .map(|step| {
+                        (
+                            (i as isize + di * step) as usize,
+                            (j as isize + dj * step) as usize,
+                        )
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+fn find_words(data: &[Vec<char>], words: &[&str], directions: &[Direction]) -> Vec<WordMatch> {
+    let mut matches = Vec::new();
+
+    for (i, row) in data.iter().enumerate() {
+        for (j, character) in row.iter().enumerate() {
+            for word in words {
+                if word.starts_with(*character) {
+                    matches.extend(walk_search_word(data, word, i, j, directions));
+                }
+            }
         }
     }
-    // up-right
-    if j + 3 < max_j && i >= 3 {
-        if data[i - 1][j + 1] == 'M' && data[i - 2][j + 2] == 'A' && data[i - 3][j + 3] == 'S' {
-            local_hits += 1;
+    matches
+}
+
+fn find_shapes(data: &[Vec<char>], shapes: &[Shape]) -> Vec<ShapeMatch> {
+    let mut matches = Vec::new();
+
+    for shape in shapes {
+        for (orientation, cells) in shape.orientations() {
+            for i in 0..data.len() {
+                for j in 0..data[i].len() {
+                    let found = cells.iter().all(|&(di, dj, c)| {
+                        char_at(data, i as isize + di, j as isize + dj) == Some(c)
+                    });
+                    if found {
+                        matches.push(ShapeMatch {
+                            name: shape.name.clone(),
+                            start: (i, j),
+                            orientation,
+                            cells: cells
+                                .iter()
+                                .map(|&(di, dj, _)| (i + di as usize, j + dj as usize))
+                                .collect(),
+                        });
+                    }
+                }
+            }
         }
     }
-    local_hits
+    matches
 }
 
-fn match_search_x_mas(data: &Vec<Vec<char>>, i: usize, j: usize, max_i: usize, max_j: usize) -> i32 {
-    if i < 1 || i + 1 >= max_i || j < 1 || j + 1 >= max_j {
-        return 0;
-    }
+fn count_by_direction(matches: &[WordMatch]) -> Vec<(Direction, usize)> {
+    Direction::ALL
+        .iter()
+        .map(|&direction| {
+            let count = matches.iter().filter(|m| m.direction == direction).count();
+            (direction, count)
+        })
+        .collect()
+}
 
-    let corners = (
-        data[i - 1][j - 1], // up-Left
-        data[i - 1][j + 1], // up-Right
-        data[i + 1][j - 1], // down-Left
-        data[i + 1][j + 1], // down-Right
-    );
+// Matched cells are drawn in bold yellow, everything else is dimmed
+fn print_highlighted(data: &[Vec<char>], covered: &HashSet<(usize, usize)>) {
+    for (i, row) in data.iter().enumerate() {
+        let line: String = row
+            .iter()
+            .enumerate()
+            .map(|(j, c)| {
+                if covered.contains(&(i, j)) {
+                    format!("\x1b[1;33m{}\x1b[0m", c)
+                } else {
+                    format!("\x1b[2m{}\x1b[0m", c)
+                }
+            })
+            .collect();
+        println!("{}", line);
+    }
+}
 
-    match corners {
-        ('M', 'M', 'S', 'S') => 1,
-        ('M', 'S', 'M', 'S') => 1,
-        ('S', 'M', 'S', 'M') => 1,
-        ('S', 'S', 'M', 'M') => 1,
-        _ => 0,
+// Only the letters that take part in no match are kept
+fn print_coverage(data: &[Vec<char>], covered: &HashSet<(usize, usize)>) {
+    for (i, row) in data.iter().enumerate() {
+        let line: String = row
+            .iter()
+            .enumerate()
+            .map(|(j, &c)| if covered.contains(&(i, j)) { '.' } else { c })
+            .collect();
+        println!("{}", line);
     }
 }
 
 fn main() {
     let data = parse_word_puzzle();
-    let start_char: char = 'X';
-    let center_char: char = 'A';
-    let mut xmas_hits: i32 = 0;
-    let mut x_mas_hits: i32 = 0;
+    let words = ["XMAS"];
+    let shapes = [Shape::from_template("X-MAS", "M.S\n.A.\nM.S")];
 
-    let max_i = data.len();
-    let max_j = data[0].len();
+    let word_matches = find_words(&data, &words, &Direction::ALL);
+    let shape_matches = find_shapes(&data, &shapes);
 
-    for (i, row) in data.iter().enumerate() {
-        for (j, _character) in row.iter().enumerate() {
-            if row[j] == start_char {
-                xmas_hits += walk_search_xmas(&data, i, j, max_i, max_j)
-            }
-            if row[j] == center_char {
-                x_mas_hits += match_search_x_mas(&data, i, j, max_i, max_j)
+    for m in &word_matches {
+        println!("{} at {:?} going {:?}", m.word, m.start, m.direction);
+    }
+    for m in &shape_matches {
+        println!(
+            "{} at {:?}, rotated {} quarter turns{}",
+            m.name,
+            m.start,
+            m.orientation.rotation,
+            if m.orientation.reflected {
+                ", reflected"
+            } else {
+                ""
             }
-        }
+        );
+    }
+    let covered: HashSet<(usize, usize)> = word_matches
+        .iter()
+        .flat_map(|m| m.cells.iter().copied())
+        .chain(shape_matches.iter().flat_map(|m| m.cells.iter().copied()))
+        .collect();
+    let total_cells: usize = data.iter().map(|row| row.len()).sum();
+
+    println!();
+    print_highlighted(&data, &covered);
+    println!();
+    println!(
+        "Cells in no match ({} of {}):",
+        total_cells - covered.len(),
+        total_cells
+    );
+    print_coverage(&data, &covered);
+    println!();
+
+    for (direction, count) in count_by_direction(&word_matches) {
+        println!("XMAS going {:?}: {}", direction, count);
     }
-    println!("XMAS instances: {}", xmas_hits);
-    println!("X-MAS instances: {}", x_mas_hits);
+    println!("XMAS instances: {}", word_matches.len());
+    println!("X-MAS instances: {}", shape_matches.len());
 }
@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
 use std::fs;
 
 fn load_print_rules() -> HashSet<(u32, u32)> {
@@ -6,10 +7,10 @@ fn load_print_rules() -> HashSet<(u32, u32)> {
     let mut order_rules = HashSet::new();
 
     for line in input.lines() {
-        if let Some((a, b)) = line.split_once('|') {
-            if let (Ok(val_a), Ok(val_b)) = (a.parse::<u32>(), b.parse::<u32>()) {
-                order_rules.insert((val_a, val_b));
-            }
+        if let Some((a, b)) = line.split_once('|')
+            && let (Ok(val_a), Ok(val_b)) = (a.parse::<u32>(), b.parse::<u32>())
+        {
+            order_rules.insert((val_a, val_b));
         }
     }
     order_rules
@@ -45,24 +46,235 @@ fn is_sorted(vec: &[u32], rules: &HashSet<(u32, u32)>) -> bool {
     true
 }
 
-fn sort_by_rules(mut vec: Vec<u32>, rules: &HashSet<(u32, u32)>) -> Vec<u32> {
-    // ordering logic...
-    vec.sort_by(|a, b| {
-        if rules.contains(&(*a, *b)) {
-            std::cmp::Ordering::Less
-        } else if rules.contains(&(*b, *a)) {
-            std::cmp::Ordering::Greater
-        } else {
-            std::cmp::Ordering::Equal
+// Kahn's algorithm over the rules that apply within this update. Pages
+// without a rule between them keep their original relative order. If the
+// rules contradict each other, the pages of one offending cycle are returned.
+fn sort_by_rules(vec: Vec<u32>, rules: &HashSet<(u32, u32)>) -> Result<Vec<u32>, Vec<u32>> {
+    topological_order(&vec, rules, &[])
+}
+
+// Same as above, but the pages at the `pinned` positions also keep their
+// relative order, as if there were rules chaining them together.
+fn topological_order(
+    vec: &[u32],
+    rules: &HashSet<(u32, u32)>,
+    pinned: &[usize],
+) -> Result<Vec<u32>, Vec<u32>> {
+    let n = vec.len();
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut in_degree = vec![0; n];
+
+    for a in 0..n {
+        for b in 0..n {
+            if a != b && rules.contains(&(vec[a], vec[b])) {
+                successors[a].push(b);
+                in_degree[b] += 1;
+            }
+        }
+    }
+    for pair in pinned.windows(2) {
+        successors[pair[0]].push(pair[1]);
+        in_degree[pair[1]] += 1;
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> =
+        (0..n).filter(|&i| in_degree[i] == 0).map(Reverse).collect();
+    let mut order = Vec::with_capacity(n);
+
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(vec[i]);
+        for &next in &successors[i] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.push(Reverse(next));
+            }
+        }
+    }
+
+    if order.len() == n {
+        return Ok(order);
+    }
+    Err(find_cycle(vec, rules, &in_degree))
+}
+
+// Every page left over by Kahn's algorithm still has a predecessor that was
+// left over too, so following predecessors must eventually revisit a page.
+fn find_cycle(vec: &[u32], rules: &HashSet<(u32, u32)>, in_degree: &[usize]) -> Vec<u32> {
+    let remaining: Vec<usize> = (0..vec.len()).filter(|&i| in_degree[i] > 0).collect();
+    let mut path: Vec<usize> = vec![remaining[0]];
+
+    loop {
+        let current = *path.last().unwrap();
+        let predecessor = *remaining
+            .iter()
+            .find(|&&p| p != current && rules.contains(&(vec[p], vec[current])))
+            .expect("Leftover page without leftover predecessor");
+
+        if let Some(pos) = path.iter().position(|&p| p == predecessor) {
+            return path[pos..].iter().rev().map(|&i| vec[i]).collect();
         }
-    });
-    vec
+        path.push(predecessor);
+    }
+}
+
+// Rule `before|after` broken by `after` being printed first
+struct Violation {
+    before: u32,
+    after: u32,
+    before_pos: usize,
+    after_pos: usize,
+}
+
+#[derive(Debug)]
+struct Move {
+    page: u32,
+    from: usize,
+    to: usize,
+}
+
+fn find_violations(vec: &[u32], rules: &HashSet<(u32, u32)>) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    for i in 0..vec.len() {
+        for j in i + 1..vec.len() {
+            if rules.contains(&(vec[j], vec[i])) {
+                violations.push(Violation {
+                    before: vec[j],
+                    after: vec[i],
+                    before_pos: j,
+                    after_pos: i,
+                });
+            }
+        }
+    }
+    violations
+}
+
+// reach[a][b] is true if some chain of rules puts page a before page b
+fn rule_reachability(vec: &[u32], rules: &HashSet<(u32, u32)>) -> Vec<Vec<bool>> {
+    let n = vec.len();
+    let mut reach: Vec<Vec<bool>> = (0..n)
+        .map(|a| (0..n).map(|b| rules.contains(&(vec[a], vec[b]))).collect())
+        .collect();
+
+    for k in 0..n {
+        for a in 0..n {
+            for b in 0..n {
+                if reach[a][k] && reach[k][b] {
+                    reach[a][b] = true;
+                }
+            }
+        }
+    }
+    reach
+}
+
+// Branch and bound over the positions that may stay where they are. Every
+// pair of kept pages must already be in an order the rules can agree with.
+fn largest_kept_set(
+    compatible: &[Vec<bool>],
+    candidates: &[usize],
+    current: &mut Vec<usize>,
+    best: &mut Vec<usize>,
+) {
+    if current.len() + candidates.len() <= best.len() {
+        return;
+    }
+    let Some((&first, rest)) = candidates.split_first() else {
+        *best = current.clone();
+        return;
+    };
+
+    let remaining: Vec<usize> = rest
+        .iter()
+        .copied()
+        .filter(|&c| compatible[first][c])
+        .collect();
+    current.push(first);
+    largest_kept_set(compatible, &remaining, current, best);
+    current.pop();
+
+    largest_kept_set(compatible, rest, current, best);
+}
+
+// Moves every page outside the largest set of pages that can stay put. The
+// returned update is the corrected order those moves produce.
+fn minimal_moves(
+    vec: &[u32],
+    rules: &HashSet<(u32, u32)>,
+) -> Result<(Vec<Move>, Vec<u32>), Vec<u32>> {
+    // Pinning the kept pages adds edges the cycle search doesn't know about,
+    // so contradicting rules have to be caught before that
+    sort_by_rules(vec.to_vec(), rules)?;
+
+    let n = vec.len();
+    let reach = rule_reachability(vec, rules);
+    let compatible: Vec<Vec<bool>> = (0..n)
+        .map(|a| {
+            (0..n)
+                .map(|b| if a < b { !reach[b][a] } else { !reach[a][b] })
+                .collect()
+        })
+        .collect();
+
+    let candidates: Vec<usize> = (0..n).collect();
+    let mut kept = Vec::new();
+    largest_kept_set(&compatible, &candidates, &mut Vec::new(), &mut kept);
+
+    let corrected = topological_order(vec, rules, &kept)?;
+    let moves = (0..n)
+        .filter(|i| !kept.contains(i))
+        .map(|i| Move {
+            page: vec[i],
+            from: i,
+            to: corrected.iter().position(|&p| p == vec[i]).unwrap(),
+        })
+        .collect();
+
+    Ok((moves, corrected))
+}
+
+fn print_diagnostics(index: usize, vec: &[u32], rules: &HashSet<(u32, u32)>) {
+    let join = |pages: &[u32]| {
+        pages
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+
+    println!("Update {}: {}", index, join(vec));
+    for v in find_violations(vec, rules) {
+        println!(
+            "  violates {}|{} ({} at position {}, {} at position {})",
+            v.before, v.after, v.after, v.after_pos, v.before, v.before_pos
+        );
+    }
+    match minimal_moves(vec, rules) {
+        Ok((moves, corrected)) => {
+            for m in &moves {
+                println!(
+                    "  move {} from position {} to position {}",
+                    m.page, m.from, m.to
+                );
+            }
+            println!("  original:  {}", join(vec));
+            println!("  corrected: {}", join(&corrected));
+        }
+        Err(cycle) => println!("  no valid order, rules form a cycle: {}", join(&cycle)),
+    }
 }
 
 fn main() {
     let rule_set = load_print_rules();
     let update_data = load_update_txt();
 
+    for (index, vec) in update_data.iter().enumerate() {
+        if !is_sorted(vec, &rule_set) {
+            print_diagnostics(index, vec, &rule_set);
+        }
+    }
+
     let mut middle_sum: u32 = update_data
         .iter()
         .filter(|vec| is_sorted(vec, &rule_set))
@@ -78,8 +290,19 @@ fn main() {
     middle_sum = update_data
         .iter()
         .filter(|vec| !is_sorted(vec, &rule_set))
-        .map(|vec| vec.clone())
-        .map(|vec| sort_by_rules(vec, &rule_set))
+        .filter_map(|vec| match sort_by_rules(vec.clone(), &rule_set) {
+            Ok(sorted) => Some(sorted),
+            Err(cycle) => {
+                let pages: Vec<String> = cycle.iter().map(|p| p.to_string()).collect();
+                println!(
+                    "Contradicting rules in update {:?}: {} -> {}",
+                    vec,
+                    pages.join(" -> "),
+                    pages[0]
+                );
+                None
+            }
+        })
         .map(|vec| vec[vec.len() / 2])
         .sum();
 
@@ -88,3 +311,34 @@ fn main() {
         middle_sum
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(pairs: &[(u32, u32)]) -> HashSet<(u32, u32)> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn minimal_moves_reports_cycle_instead_of_panicking() {
+        let rules = rules(&[
+            (10, 15),
+            (10, 11),
+            (10, 14),
+            (12, 10),
+            (15, 12),
+            (14, 15),
+            (13, 11),
+        ]);
+        let update = [11, 14, 15, 12, 13, 10];
+
+        let cycle = minimal_moves(&update, &rules).expect_err("rules contain a cycle");
+        assert!(cycle.len() > 1);
+        assert!(cycle.iter().all(|page| update.contains(page)));
+        for pair in cycle.windows(2) {
+            assert!(rules.contains(&(pair[0], pair[1])));
+        }
+        assert!(rules.contains(&(*cycle.last().unwrap(), cycle[0])));
+    }
+}
@@ -1,5 +1,7 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
+use std::thread;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 enum Direction {
@@ -9,6 +11,129 @@ enum Direction {
     West,
 }
 
+impl Direction {
+    fn turn_right(self) -> Direction {
+        match self {
+            Direction::North => Direction::East,
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+        }
+    }
+    fn from_marker(ch: char) -> Option<Direction> {
+        match ch {
+            '^' => Some(Direction::North),
+            '>' => Some(Direction::East),
+            'v' => Some(Direction::South),
+            '<' => Some(Direction::West),
+            _ => None,
+        }
+    }
+    fn index(self) -> usize {
+        match self {
+            Direction::North => 0,
+            Direction::East => 1,
+            Direction::South => 2,
+            Direction::West => 3,
+        }
+    }
+}
+
+// For every tile and direction, the tile the guard ends up on in front of
+// the next obstacle, or None if it walks off the map instead.
+struct JumpTable {
+    width: usize,
+    stops: [Vec<Option<(usize, usize)>>; 4],
+}
+
+impl JumpTable {
+    fn new(map_grid: &[Vec<char>]) -> Self {
+        let height = map_grid.len();
+        let width = map_grid[0].len();
+        let mut stops: [Vec<Option<(usize, usize)>>; 4] =
+            std::array::from_fn(|_| vec![None; width * height]);
+
+        for x in 0..width {
+            let mut stop = None;
+            for y in 0..height {
+                stops[Direction::North.index()][y * width + x] = stop;
+                if map_grid[y][x] == '#' {
+                    stop = Some((x, y + 1));
+                }
+            }
+            stop = None;
+            for y in (0..height).rev() {
+                stops[Direction::South.index()][y * width + x] = stop;
+                if map_grid[y][x] == '#' && y > 0 {
+                    stop = Some((x, y - 1));
+                }
+            }
+        }
+        for y in 0..height {
+            let mut stop = None;
+            for x in 0..width {
+                stops[Direction::West.index()][y * width + x] = stop;
+                if map_grid[y][x] == '#' {
+                    stop = Some((x + 1, y));
+                }
+            }
+            stop = None;
+            for x in (0..width).rev() {
+                stops[Direction::East.index()][y * width + x] = stop;
+                if map_grid[y][x] == '#' && x > 0 {
+                    stop = Some((x - 1, y));
+                }
+            }
+        }
+        JumpTable { width, stops }
+    }
+
+    // Where the guard turns next, taking one extra obstacle into account
+    fn jump(
+        &self,
+        (x, y): (usize, usize),
+        direction: Direction,
+        extra_obstacle: Option<(usize, usize)>,
+    ) -> Option<(usize, usize)> {
+        let stop = self.stops[direction.index()][y * self.width + x];
+
+        let extra_stop = match (direction, extra_obstacle) {
+            (Direction::North, Some((ox, oy))) if ox == x && oy < y => Some((x, oy + 1)),
+            (Direction::South, Some((ox, oy))) if ox == x && oy > y => Some((x, oy - 1)),
+            (Direction::East, Some((ox, oy))) if oy == y && ox > x => Some((ox - 1, y)),
+            (Direction::West, Some((ox, oy))) if oy == y && ox < x => Some((ox + 1, y)),
+            _ => None,
+        };
+
+        let distance = |(sx, sy): (usize, usize)| sx.abs_diff(x) + sy.abs_diff(y);
+        match (stop, extra_stop) {
+            (Some(a), Some(b)) => Some(if distance(b) < distance(a) { b } else { a }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+// Tile occupied after each step. A looping guard repeats
+// positions[cycle_start..] forever, any other guard has left the map.
+struct Route {
+    positions: Vec<(usize, usize)>,
+    cycle_start: Option<usize>,
+}
+
+impl Route {
+    fn position_at(&self, t: usize) -> Option<(usize, usize)> {
+        if t < self.positions.len() {
+            return Some(self.positions[t]);
+        }
+        let start = self.cycle_start?;
+        let period = self.positions.len() - start;
+        Some(self.positions[start + (t - start) % period])
+    }
+    fn tiles(&self) -> HashSet<(usize, usize)> {
+        self.positions.iter().copied().collect()
+    }
+}
+
 struct Guard {
     x: usize,
     y: usize,
@@ -16,23 +141,53 @@ struct Guard {
     direction: Direction,
     arrived: bool,
     obstacle_locations: Vec<(usize, usize)>,
+    jump_table: JumpTable,
 }
 
 impl Guard {
+    // Stops when the guard leaves the map or repeats a position and direction
     fn walk(&mut self) {
-        let mut current_steps: u32 = 0;
+        let mut visited_states = vec![false; self.state_count()];
         println!("Start walking...");
 
         while !self.arrived {
+            let state = self.state_index((self.x, self.y), self.direction);
+            if visited_states[state] {
+                println!("The guard is stuck in a loop and never leaves the map.");
+                break;
+            }
+            visited_states[state] = true;
             self.step(false);
-            current_steps += 1;
+        }
+    }
+    // Same walk as above without drawing, one entry per step (turns included)
+    fn trace_route(&mut self) -> Route {
+        let mut first_seen = vec![None; self.state_count()];
+        let mut positions = Vec::new();
 
-            if current_steps >= 100000 {
-                println!("Something feels off. Force quit...");
-                break;
+        while !self.arrived {
+            let state = self.state_index((self.x, self.y), self.direction);
+            if let Some(t) = first_seen[state] {
+                return Route {
+                    positions,
+                    cycle_start: Some(t),
+                };
             }
+            first_seen[state] = Some(positions.len());
+            positions.push((self.x, self.y));
+            self.step(true);
+        }
+        Route {
+            positions,
+            cycle_start: None,
         }
     }
+    fn state_count(&self) -> usize {
+        self.map_grid.len() * self.map_grid[0].len() * 4
+    }
+    fn state_index(&self, (x, y): (usize, usize), direction: Direction) -> usize {
+        (y * self.map_grid[0].len() + x) * 4 + direction.index()
+    }
     fn step(&mut self, is_test: bool) {
         let map_x: usize = self.map_grid[0].len();
         let map_y: usize = self.map_grid.len();
@@ -61,12 +216,7 @@ impl Guard {
         }
     }
     fn change_direction(&mut self) {
-        match self.direction {
-            Direction::North => self.direction = Direction::East,
-            Direction::East => self.direction = Direction::South,
-            Direction::South => self.direction = Direction::West,
-            Direction::West => self.direction = Direction::North,
-        }
+        self.direction = self.direction.turn_right();
     }
     fn update_location_tile(&mut self) {
         match self.direction {
@@ -76,34 +226,42 @@ impl Guard {
             Direction::West => self.map_grid[self.y][self.x] = '<',
         }
     }
-    fn looping_path(&mut self) -> bool {
+    // Jumps from turn to turn instead of walking tile by tile
+    fn looping_path(&self, start: (usize, usize, Direction), obstacle: (usize, usize)) -> bool {
+        // Only turns are recorded, so a set stays far smaller than the grid.
+        // There are at most state_count() of them, so this always terminates.
         let mut visited_states = HashSet::new();
+        let (mut position, mut direction) = ((start.0, start.1), start.2);
 
-        while !self.arrived {
-            let current_state = (self.x, self.y, self.direction);
-
-            if visited_states.contains(&current_state) {
+        while let Some(stop) = self.jump_table.jump(position, direction, Some(obstacle)) {
+            if !visited_states.insert(self.state_index(stop, direction)) {
                 return true;
             }
-            visited_states.insert(current_state);
 
-            self.step(true);
-
-            if visited_states.len() > 10000 {
-                break;
-            }
+            position = stop;
+            direction = direction.turn_right();
         }
         false
     }
-    fn search_cycles(&mut self) {
-        println!("Start cycle search...");
+    // Candidates are gathered on the real walk first, then tested in
+    // parallel: each test only reads the map and adds one virtual obstacle.
+    fn search_cycles(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        println!("Start cycle search on {} thread(s)...", threads);
 
         let start_x = self.x;
         let start_y = self.y;
         let start_dir = self.direction;
-        let mut possible_obstructions = HashSet::new();
+        let mut candidates = Vec::new();
+        let mut visited_states = vec![false; self.state_count()];
 
         while !self.arrived {
+            let state = self.state_index((self.x, self.y), self.direction);
+            if visited_states[state] {
+                break;
+            }
+            visited_states[state] = true;
+
             let (tx, ty) = match self.direction {
                 Direction::North if self.y > 0 => (self.x, self.y - 1),
                 Direction::East if self.x < self.map_grid[0].len() - 1 => (self.x + 1, self.y),
@@ -116,26 +274,34 @@ impl Guard {
             };
             // If the space in front is empty, try putting an obstacle there!.
             if self.map_grid[ty][tx] == '.' && (tx != start_x || ty != start_y) {
-                let saved_state = (self.x, self.y, self.direction);
-
-                // Place obstacle
-                self.map_grid[ty][tx] = '#';
-                self.arrived = false;
-
-                // Resetting to start
-                self.move_to((start_x, start_y, start_dir));
-
-                if self.looping_path() {
-                    possible_obstructions.insert((ty, tx));
-                }
-                // CLEANUP
-                self.map_grid[ty][tx] = '.';
-                self.move_to(saved_state);
+                candidates.push((tx, ty));
             }
 
             // Move forward on the "real" path
             self.step(false);
         }
+
+        let start = (start_x, start_y, start_dir);
+        let chunk_size = candidates.len().div_ceil(threads).max(1);
+        let guard: &Guard = self;
+        let possible_obstructions: HashSet<(usize, usize)> = thread::scope(|scope| {
+            let workers: Vec<_> = candidates
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter(|&&obstacle| guard.looping_path(start, obstacle))
+                            .map(|&(tx, ty)| (ty, tx))
+                            .collect::<Vec<(usize, usize)>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Cycle search thread panicked"))
+                .collect()
+        });
         self.obstacle_locations = possible_obstructions.into_iter().collect();
     }
     fn move_to(&mut self, state: (usize, usize, Direction)) {
@@ -171,21 +337,123 @@ fn load_map() -> Vec<Vec<char>> {
     data
 }
 
-fn find_start_coordinates(map_data: &Vec<Vec<char>>) -> Option<(usize, usize)> {
+fn find_guards(map_data: &[Vec<char>]) -> Vec<(usize, usize, Direction)> {
+    let mut guards = Vec::new();
     for (i, vec) in map_data.iter().enumerate() {
         for (j, ch) in vec.iter().enumerate() {
-            if *ch == '^' {
-                return Some((j, i));
+            if let Some(direction) = Direction::from_marker(*ch) {
+                guards.push((j, i, direction));
             }
         }
     }
-    None
+    guards
+}
+
+// Checks every step until both routes are periodic (or one guard is gone).
+// After that a shared tile at cycle offsets i and j is reached at the same
+// time exactly when the two offsets agree modulo gcd of the periods.
+fn find_meeting(a: &Route, b: &Route) -> Option<(usize, usize)> {
+    let horizon = a.positions.len().max(b.positions.len());
+    for t in 0..horizon {
+        if let (Some(pa), Some(pb)) = (a.position_at(t), b.position_at(t))
+            && pa == pb
+        {
+            return Some(pa);
+        }
+    }
+
+    let (start_a, start_b) = (a.cycle_start?, b.cycle_start?);
+    let period_a = a.positions.len() - start_a;
+    let period_b = b.positions.len() - start_b;
+    let divisor = gcd(period_a, period_b);
+
+    let mut offsets_b: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (offset, &tile) in b.positions[start_b..].iter().enumerate() {
+        offsets_b
+            .entry(tile)
+            .or_default()
+            .push((start_b + offset) % divisor);
+    }
+    a.positions[start_a..]
+        .iter()
+        .enumerate()
+        .find(|(offset, tile)| {
+            offsets_b
+                .get(*tile)
+                .is_some_and(|residues| residues.contains(&((start_a + offset) % divisor)))
+        })
+        .map(|(_, &tile)| tile)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn report_guards(map_grid: &[Vec<char>], guards: &[(usize, usize, Direction)]) {
+    let routes: Vec<Route> = guards
+        .iter()
+        .map(|&(x, y, direction)| {
+            let mut guard = Guard {
+                x,
+                y,
+                map_grid: map_grid.to_vec(),
+                direction,
+                arrived: false,
+                obstacle_locations: Vec::new(),
+                jump_table: JumpTable::new(map_grid),
+            };
+            guard.trace_route()
+        })
+        .collect();
+
+    let mut tile_guards: HashMap<(usize, usize), usize> = HashMap::new();
+    for (n, (route, &(x, y, direction))) in routes.iter().zip(guards).enumerate() {
+        let tiles = route.tiles();
+        println!(
+            "Guard {} at {} {} facing {:?}: {} tiles{}",
+            n + 1,
+            x,
+            y,
+            direction,
+            tiles.len(),
+            if route.cycle_start.is_some() {
+                ", stuck in a loop"
+            } else {
+                ""
+            }
+        );
+        for tile in tiles {
+            *tile_guards.entry(tile).or_default() += 1;
+        }
+    }
+    println!(
+        "Tiles covered by more than one guard: {}",
+        tile_guards.values().filter(|&&count| count > 1).count()
+    );
+
+    let mut any_meeting = false;
+    for a in 0..routes.len() {
+        for b in a + 1..routes.len() {
+            if let Some((x, y)) = find_meeting(&routes[a], &routes[b]) {
+                println!("Guards {} and {} meet on tile {} {}", a + 1, b + 1, x, y);
+                any_meeting = true;
+            }
+        }
+    }
+    if routes.len() > 1 && !any_meeting {
+        println!("No two guards ever meet on a tile");
+    }
 }
 
 fn main() {
-    let map_grid = load_map();
-    let (start_x, start_y) =
-        find_start_coordinates(&map_grid).expect("CRITICAL: No entry point found!");
+    let mut map_grid = load_map();
+    let guards = find_guards(&map_grid);
+    let &(start_x, start_y, start_dir) = guards.first().expect("CRITICAL: No entry point found!");
+
+    // Guards don't block each other, so their markers are just floor
+    for &(x, y, _) in &guards {
+        map_grid[y][x] = '.';
+    }
 
     println!("Start coordinates: {} {}", start_x, start_y);
 
@@ -194,9 +462,10 @@ fn main() {
         x: start_x,
         y: start_y,
         map_grid: map_grid.clone(),
-        direction: Direction::North,
+        direction: start_dir,
         arrived: false,
         obstacle_locations: Vec::new(),
+        jump_table: JumpTable::new(&map_grid),
     };
 
     // Walk to the end normally and track all the visited tiles
@@ -206,10 +475,17 @@ fn main() {
 
     // Reset the guard and search for potential loops
     guard.map_grid = map_grid.clone();
-    guard.move_to((start_x, start_y, Direction::North));
-    guard.search_cycles();
+    guard.move_to((start_x, start_y, start_dir));
+    // Optional first argument: number of worker threads for the cycle search
+    let threads = env::args()
+        .nth(1)
+        .map(|arg| arg.parse::<usize>().expect("Thread count must be a number"))
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    guard.search_cycles(threads);
     println!(
         "Obstacles implying loops: {}",
         guard.obstacle_locations.len()
     );
+
+    report_guards(&map_grid, &guards);
 }
@@ -1,79 +1,249 @@
+use std::env;
 use std::fs;
 
-fn read_equations() -> Vec<Vec<u64>> {
-    let input = fs::read_to_string("puzzle_input.txt").expect("If only I had a file... .__.");
+// Every non-empty line is `target: operand operand ...`; each number has
+// to fit in a u128, so nothing is silently dropped or reinterpreted
+fn read_equations(input: &str) -> Result<Vec<Vec<u128>>, String> {
+    let mut data: Vec<Vec<u128>> = Vec::new();
 
-    let data: Vec<Vec<u64>> = input
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            line.replace(":", "")
-                .split_whitespace()
-                .filter_map(|s| s.parse::<u64>().ok())
-                .collect()
-        })
-        .collect();
+    for (line_idx, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parse = |s: &str| {
+            s.parse::<u128>()
+                .map_err(|e| format!("line {}: invalid number {:?} ({})", line_idx + 1, s, e))
+        };
+        let (target, operands) = line
+            .split_once(':')
+            .ok_or_else(|| format!("line {}: missing ':' after the target", line_idx + 1))?;
 
-    data
+        let mut equation = vec![parse(target.trim())?];
+        for operand in operands.split_whitespace() {
+            equation.push(parse(operand)?);
+        }
+        if equation.len() < 2 {
+            return Err(format!("line {}: no operands", line_idx + 1));
+        }
+        data.push(equation);
+    }
+    Ok(data)
 }
 
-fn concat_digits(a: u64, b: u64) -> u64 {
-    let mut a_str: String = a.to_string();
-    let b_str: String = b.to_string();
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Add,
+    Multiply,
+    Concat,
+    Subtract,
+    Power,
+}
 
-    a_str.push_str(&b_str);
-    a_str
-        .parse::<u64>()
-        .expect("Failed to parse concatenated digits.")
+impl Operator {
+    fn from_symbol(symbol: &str) -> Option<Operator> {
+        match symbol {
+            "+" => Some(Operator::Add),
+            "*" => Some(Operator::Multiply),
+            "||" => Some(Operator::Concat),
+            "-" => Some(Operator::Subtract),
+            "^" => Some(Operator::Power),
+            _ => None,
+        }
+    }
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Multiply => "*",
+            Operator::Concat => "||",
+            Operator::Subtract => "-",
+            Operator::Power => "^",
+        }
+    }
+    // None if the result would overflow or go negative
+    fn apply(self, a: u128, b: u128) -> Option<u128> {
+        match self {
+            Operator::Add => a.checked_add(b),
+            Operator::Multiply => a.checked_mul(b),
+            Operator::Concat => concat_digits(a, b),
+            Operator::Subtract => a.checked_sub(b),
+            Operator::Power => a.checked_pow(u32::try_from(b).ok()?),
+        }
+    }
+    // The value `a` with `a op operand == target`, if there is exactly one
+    fn undo(self, target: u128, operand: u128) -> Option<u128> {
+        match self {
+            Operator::Add => target.checked_sub(operand),
+            Operator::Multiply if operand != 0 && target.is_multiple_of(operand) => {
+                Some(target / operand)
+            }
+            Operator::Multiply => None,
+            Operator::Concat => {
+                let shift = 10u128.checked_pow(digit_count(operand))?;
+                (target % shift == operand).then_some(target / shift)
+            }
+            Operator::Subtract => target.checked_add(operand),
+            Operator::Power => integer_root(target, u32::try_from(operand).ok()?),
+        }
+    }
+    // `a * 0` and `a ^ 0` give the same result for every `a`
+    fn absorbs(self, target: u128, operand: u128) -> bool {
+        matches!(
+            (self, operand, target),
+            (Operator::Multiply, 0, 0) | (Operator::Power, 0, 1)
+        )
+    }
 }
 
-fn check_equation_viability(result: u64, current_value: u64, remaining_values: &[u64]) -> bool {
-    let next_value = remaining_values[0];
+fn digit_count(mut n: u128) -> u32 {
+    let mut digits = 1;
+    while n >= 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+fn concat_digits(a: u128, b: u128) -> Option<u128> {
+    a.checked_mul(10u128.checked_pow(digit_count(b))?)?
+        .checked_add(b)
+}
+
+// Exact k-th root by binary search, None if `n` is not a perfect power
+fn integer_root(n: u128, k: u32) -> Option<u128> {
+    if k == 0 {
+        return None;
+    }
+    let (mut low, mut high) = (0u128, n);
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        match mid.checked_pow(k) {
+            Some(p) if p == n => return Some(mid),
+            Some(p) if p < n => low = mid + 1,
+            _ => high = mid.checked_sub(1)?,
+        }
+    }
+    None
+}
 
-    if current_value > result {
+// Works from the last operand back to the first, undoing one operator at a
+// time: divisibility for *, subtraction for + and a digit suffix for ||.
+// Most branches die immediately, so long equations stay fast. On success,
+// `used` holds the operator sequence in reverse.
+fn check_equation_viability(
+    target: u128,
+    values: &[u128],
+    operators: &[Operator],
+    used: &mut Vec<Operator>,
+) -> bool {
+    let Some((&last, prefix)) = values.split_last() else {
         return false;
+    };
+    if prefix.is_empty() {
+        return target == last;
     }
-    if remaining_values.len() == 1 {
-        if result == current_value + next_value
-            || result == current_value * next_value
-            || result == concat_digits(current_value, next_value)
-        {
-            return true;
-        } else {
-            return false;
+
+    for &op in operators {
+        if let Some(previous) = op.undo(target, last) {
+            used.push(op);
+            if check_equation_viability(previous, prefix, operators, used) {
+                return true;
+            }
+            used.pop();
+        } else if op.absorbs(target, last) {
+            // The prefix only has to evaluate to something
+            let mut forward = Vec::new();
+            if any_evaluation(prefix[0], &prefix[1..], operators, &mut forward) {
+                used.push(op);
+                used.extend(forward.into_iter().rev());
+                return true;
+            }
         }
-    } else {
-        check_equation_viability(
-            result,
-            current_value + next_value,
-            &remaining_values[1..remaining_values.len()],
-        ) || check_equation_viability(
-            result,
-            current_value * next_value,
-            &remaining_values[1..remaining_values.len()],
-        ) || check_equation_viability(
-            result,
-            concat_digits(current_value, next_value),
-            &remaining_values[1..remaining_values.len()],
-        )
     }
+    false
 }
 
-fn main() {
-    let equations = read_equations();
-    let mut valid_results: Vec<u64> = Vec::new();
+// Finds any operator sequence that evaluates without overflow or going negative
+fn any_evaluation(
+    current_value: u128,
+    remaining_values: &[u128],
+    operators: &[Operator],
+    used: &mut Vec<Operator>,
+) -> bool {
+    let Some((&next_value, rest)) = remaining_values.split_first() else {
+        return true;
+    };
+    for &op in operators {
+        if let Some(value) = op.apply(current_value, next_value) {
+            used.push(op);
+            if any_evaluation(value, rest, operators, used) {
+                return true;
+            }
+            used.pop();
+        }
+    }
+    false
+}
+
+fn format_witness(result: u128, values: &[u128], used: &[Operator]) -> String {
+    let mut witness = format!("{} = {}", result, values[0]);
+    for (op, value) in used.iter().zip(&values[1..]) {
+        witness.push_str(&format!(" {} {}", op.symbol(), value));
+    }
+    witness
+}
+
+fn solve_all(equations: &[Vec<u128>], operators: &[Operator]) -> u128 {
+    let mut valid_results: Vec<u128> = Vec::new();
 
     for eq in equations.iter() {
         if let Some((result, values)) = eq.split_first() {
-            if values.len() == 1 && values[0] == *result {
-                valid_results.push(*result);
-            } else if check_equation_viability(*result, values[0], &values[1..values.len()]) {
+            let mut used = Vec::new();
+            if check_equation_viability(*result, values, operators, &mut used) {
+                used.reverse();
+                println!("  {}", format_witness(*result, values, &used));
                 valid_results.push(*result);
             }
         }
     }
-    println!(
-        "Sum of valid results: {}",
-        valid_results.into_iter().sum::<u64>()
-    );
+    valid_results.into_iter().sum::<u128>()
+}
+
+fn main() {
+    let input = fs::read_to_string("puzzle_input.txt").expect("If only I had a file... .__.");
+    let equations = match read_equations(&input) {
+        Ok(equations) => equations,
+        Err(err) => {
+            println!("Could not read the equations: {}", err);
+            return;
+        }
+    };
+    let mut operator_sets = vec![
+        ("Part 1", vec![Operator::Add, Operator::Multiply]),
+        (
+            "Part 2",
+            vec![Operator::Add, Operator::Multiply, Operator::Concat],
+        ),
+    ];
+
+    // Optional arguments: a custom operator set, e.g. `cargo run -- + - ^`
+    let custom: Vec<Operator> = env::args()
+        .skip(1)
+        .map(|arg| {
+            Operator::from_symbol(&arg)
+                .unwrap_or_else(|| panic!("Unknown operator: {} (use + * || - ^)", arg))
+        })
+        .collect();
+    if !custom.is_empty() {
+        operator_sets.push(("Custom", custom));
+    }
+
+    for (name, operators) in &operator_sets {
+        let symbols: Vec<&str> = operators.iter().map(|op| op.symbol()).collect();
+        println!("{} with {{{}}}:", name, symbols.join(", "));
+        println!(
+            "{} - Sum of valid results: {}",
+            name,
+            solve_all(&equations, operators)
+        );
+    }
 }
@@ -1,8 +1,8 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::collections::HashSet;
+use std::env;
 use std::fs;
 
-
 fn parse_antenna_map() -> Vec<Vec<char>> {
     let input = fs::read_to_string("puzzle_input.txt").expect("There is no map file?");
 
@@ -14,14 +14,14 @@ fn parse_antenna_map() -> Vec<Vec<char>> {
     data
 }
 
-fn print_map(map_data: &Vec<Vec<char>>) {
-    println!("\n");
+fn print_map(map_data: &[Vec<char>]) {
+    println!();
     for vec in map_data.iter() {
-        println!("{:?}", vec);
+        println!("{}", vec.iter().collect::<String>());
     }
 }
 
-fn find_antenna_locations(map_data: &Vec<Vec<char>>) -> HashMap<char, Vec<(i32, i32)>> { 
+fn find_antenna_locations(map_data: &[Vec<char>]) -> HashMap<char, Vec<(i32, i32)>> {
     let mut location_data: HashMap<char, Vec<(i32, i32)>> = HashMap::new();
 
     for (i, vec) in map_data.iter().enumerate() {
@@ -46,13 +46,22 @@ fn apply_vector(a: (i32, i32), b: (i32, i32)) -> (i32, i32) {
     (a.0 + b.0, a.1 + b.1)
 }
 
-fn get_antenna_vectors(coordinates: &Vec<(i32, i32)>, map_data: &Vec<Vec<char>>, harmonic_model: bool) -> Vec<(i32, i32)> {
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+fn get_antenna_vectors(
+    coordinates: &[(i32, i32)],
+    map_data: &[Vec<char>],
+    harmonic_model: bool,
+) -> Vec<(i32, i32)> {
     let mut antenna_vectors: Vec<(i32, i32)> = Vec::new();
     let max_i = (map_data.len() - 1) as i32;
     let max_j = (map_data[0].len() - 1) as i32;
 
     // The logic works like this: We calculate the vectors between any two towers sharing a symbol ("frequency").
-    // Then we extend the line crossing both towers either once (Part I) or until we hit the edge of the map (Part II) in both directions
+    // Then we extend the line crossing both towers either once (Part I) or until we hit the edge of the map (Part II) in both directions.
+    // For Part II the step is the difference vector divided by its gcd, so grid points between the towers are included too.
     for i in 0..coordinates.len() {
         for j in i + 1..coordinates.len() {
             let (mut a1, mut a2) = coordinates[i];
@@ -63,6 +72,8 @@ fn get_antenna_vectors(coordinates: &Vec<(i32, i32)>, map_data: &Vec<Vec<char>>,
                 antenna_vectors.push(apply_vector((a1, a2), (-x, -y)));
                 antenna_vectors.push(apply_vector((b1, b2), (x, y)));
             } else {
+                let divisor = gcd(x, y);
+                let (x, y) = (x / divisor, y / divisor);
                 while a1 >= 0 && a2 >= 0 && a1 <= max_i && a2 <= max_j {
                     antenna_vectors.push((a1, a2));
                     (a1, a2) = apply_vector((a1, a2), (-x, -y))
@@ -79,15 +90,15 @@ fn get_antenna_vectors(coordinates: &Vec<(i32, i32)>, map_data: &Vec<Vec<char>>,
 }
 
 fn calculate_signals(
-    data: HashMap<char, Vec<(i32, i32)>>,
-    map_data: &Vec<Vec<char>>,
+    data: &HashMap<char, Vec<(i32, i32)>>,
+    map_data: &[Vec<char>],
     harmonic_model: bool,
 ) -> HashMap<char, Vec<(i32, i32)>> {
     let mut signal_locations: HashMap<char, Vec<(i32, i32)>> = HashMap::new();
 
-    for (id, coordinates) in &data {
+    for (id, coordinates) in data {
         if coordinates.len() > 1 {
-            let antenna_signals = get_antenna_vectors(&coordinates, &map_data, harmonic_model);
+            let antenna_signals = get_antenna_vectors(coordinates, map_data, harmonic_model);
 
             for (i, j) in &antenna_signals {
                 signal_locations.entry(*id).or_default().push((*i, *j));
@@ -97,34 +108,65 @@ fn calculate_signals(
     signal_locations
 }
 
-fn map_signals(signal_locations: HashMap<char, Vec<(i32, i32)>>, mut map_data: Vec<Vec<char>>) {
+// Every antinode tile on the map, with the frequencies that produce it
+fn antinode_frequencies(
+    signal_locations: &HashMap<char, Vec<(i32, i32)>>,
+    map_data: &[Vec<char>],
+) -> BTreeMap<(i32, i32), Vec<char>> {
     let max_i = (map_data.len() - 1) as i32;
     let max_j = (map_data[0].len() - 1) as i32;
-    let mut valid_signals = 0;
-
-    let mut mapped_locations = HashSet::new();
-
-    for (_id, coordinates) in &signal_locations {
-        for (i, j) in coordinates {
-            if *i >= 0 && *i <= max_i && *j >= 0 && *j <= max_j {
-                match map_data[*i as usize][*j as usize] {
-                    '.' => {
-                        map_data[*i as usize][*j as usize] = '#';
-                        mapped_locations.insert((i, j));
-                        valid_signals += 1;
-                    }
-                    '#' => {}
-                    _ if !mapped_locations.contains(&(i, j)) => {
-                        mapped_locations.insert((i, j));
-                        valid_signals += 1;
-                    }
-                    _ => {}
+    let mut tiles: BTreeMap<(i32, i32), Vec<char>> = BTreeMap::new();
+
+    for (id, coordinates) in signal_locations {
+        for &(i, j) in coordinates {
+            if i >= 0 && i <= max_i && j >= 0 && j <= max_j {
+                let frequencies = tiles.entry((i, j)).or_default();
+                if !frequencies.contains(id) {
+                    frequencies.push(*id);
+                    frequencies.sort();
                 }
             }
         }
     }
-    // print_map(&map_data);
-    println!("Discovered signal locations: {}", valid_signals);
+    tiles
+}
+
+// Antennas stay as they are, antinodes become '#', or '*' where frequencies overlap
+fn annotate_map(tiles: &BTreeMap<(i32, i32), Vec<char>>, map_data: &[Vec<char>]) -> Vec<Vec<char>> {
+    let mut annotated = map_data.to_vec();
+
+    for (&(i, j), frequencies) in tiles {
+        let tile = &mut annotated[i as usize][j as usize];
+        if *tile == '.' || *tile == '#' {
+            *tile = if frequencies.len() > 1 { '*' } else { '#' };
+        }
+    }
+    annotated
+}
+
+fn report_frequencies(tiles: &BTreeMap<(i32, i32), Vec<char>>, map_data: &[Vec<char>]) {
+    let mut per_frequency: BTreeMap<char, usize> = BTreeMap::new();
+    for frequency in tiles.values().flatten() {
+        *per_frequency.entry(*frequency).or_default() += 1;
+    }
+    for (frequency, count) in &per_frequency {
+        println!("  Frequency '{}': {} antinodes", frequency, count);
+    }
+
+    for (&(i, j), frequencies) in tiles {
+        if frequencies.len() > 1 {
+            let ids: String = frequencies.iter().collect();
+            println!("  Overlap at ({}, {}): frequencies {}", i, j, ids);
+        }
+        let antenna = map_data[i as usize][j as usize];
+        if antenna != '.' && antenna != '#' {
+            let ids: String = frequencies.iter().collect();
+            println!(
+                "  Antinode of {} on antenna '{}' at ({}, {})",
+                ids, antenna, i, j
+            );
+        }
+    }
 }
 
 fn main() {
@@ -132,6 +174,28 @@ fn main() {
     // print_map(&map_data);
 
     let location_data = find_antenna_locations(&map_data);
-    let signal_locations = calculate_signals(location_data, &map_data, true);
-    map_signals(signal_locations, map_data.clone());
+
+    // Optional argument: file to write the annotated harmonics map to
+    let output_file = env::args().nth(1);
+
+    for (name, harmonic_model) in [("", false), (" (harmonics)", true)] {
+        let signal_locations = calculate_signals(&location_data, &map_data, harmonic_model);
+        let tiles = antinode_frequencies(&signal_locations, &map_data);
+        println!("Discovered signal locations{}: {}", name, tiles.len());
+
+        report_frequencies(&tiles, &map_data);
+
+        let annotated = annotate_map(&tiles, &map_data);
+        print_map(&annotated);
+        println!();
+
+        if harmonic_model && let Some(path) = &output_file {
+            let rendered: String = annotated
+                .iter()
+                .map(|row| row.iter().collect::<String>() + "\n")
+                .collect();
+            fs::write(path, rendered).expect("Could not write the map file");
+            println!("Annotated map written to {}", path);
+        }
+    }
 }
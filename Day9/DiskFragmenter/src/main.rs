@@ -1,65 +1,92 @@
+use std::collections::BTreeSet;
+use std::env;
 use std::fs;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum MemoryAddress {
-    FileNumber(u16),
+    FileNumber(usize),
     Empty,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct MemorySegment {
-    filenumber: Option<u16>,
-    length: u16,
+    filenumber: Option<usize>,
+    length: usize,
 }
 
+// Blocks are padded to the widest file number and separated by spaces,
+// so multi-digit file numbers stay readable and states line up.
 impl MemorySegment {
-    fn print_length(&self) {
-        print!("{}", self.length);
-    }
-    fn print_segment(&self) {
+    fn print_segment(&self, width: usize) {
         let token = match self.filenumber {
             Some(val) => val.to_string(),
             None => ".".to_string(),
         };
         for _i in 0..self.length {
-            print!("{}", token);
+            print!("{:>width$} ", token);
         }
     }
 }
 
-fn print_disk_map(disk_map: &Vec<MemoryAddress>) {
+fn print_disk_map(disk_map: &[MemoryAddress], width: usize) {
     for address in disk_map.iter() {
         match address {
-            MemoryAddress::Empty => print!("."),
-            MemoryAddress::FileNumber(filenumber) => print!("{}", filenumber),
+            MemoryAddress::Empty => print!("{:>width$} ", "."),
+            MemoryAddress::FileNumber(filenumber) => print!("{:>width$} ", filenumber),
         }
     }
     println!();
 }
 
-fn print_segment_map(segment_map: &Vec<MemorySegment>) {
+fn print_segment_map(segment_map: &[MemorySegment], width: usize) {
     for segment in segment_map.iter() {
-        segment.print_segment();
+        segment.print_segment(width);
     }
     println!();
 }
 
-fn read_disk_map() -> Vec<u16> {
+fn filenumber_width(segment_map: &[MemorySegment]) -> usize {
+    let max_id = segment_map
+        .iter()
+        .filter_map(|s| s.filenumber)
+        .max()
+        .unwrap_or(0);
+    max_id.to_string().len()
+}
+
+fn read_disk_map() -> Vec<usize> {
     let input = fs::read_to_string("puzzle_input.txt").expect("There is no map file?");
-    let data: Vec<u16> = input
-        .lines()
-        .flat_map(|line| {
-            line.chars()
-                .filter_map(|c| c.to_digit(10).map(|d| d as u16))
-        })
-        .collect();
+    parse_disk_map(&input).unwrap_or_else(|err| panic!("Malformed disk map: {}", err))
+}
+
+// Every character apart from line breaks has to be a digit
+fn parse_disk_map(input: &str) -> Result<Vec<usize>, String> {
+    let mut data: Vec<usize> = Vec::new();
 
-    data
+    for (line_idx, line) in input.lines().enumerate() {
+        for (col_idx, c) in line.chars().enumerate() {
+            match c.to_digit(10) {
+                Some(d) => data.push(d as usize),
+                None => {
+                    return Err(format!(
+                        "unexpected character {:?} at line {}, column {}",
+                        c,
+                        line_idx + 1,
+                        col_idx + 1
+                    ));
+                }
+            }
+        }
+    }
+    if data.is_empty() {
+        return Err("no digits found".to_string());
+    }
+    Ok(data)
 }
 
-fn expand_disk_map(data: &Vec<u16>) -> Vec<MemoryAddress> {
+fn expand_disk_map(data: &[usize]) -> Vec<MemoryAddress> {
     let mut disk_map: Vec<MemoryAddress> = Vec::new();
-    let mut filenumber: u16 = 0;
+    let mut filenumber: usize = 0;
     let mut is_file: bool = true;
 
     for entry in data.iter() {
@@ -81,7 +108,7 @@ fn expand_disk_map(data: &Vec<u16>) -> Vec<MemoryAddress> {
 
 fn refomat_disk_single_address(mut disk_map: Vec<MemoryAddress>) -> Vec<MemoryAddress> {
     let mut i: usize = 0;
-    let mut j: usize = disk_map.len() - 1;
+    let mut j: usize = disk_map.len().saturating_sub(1);
 
     while i < j {
         // print_disk_map(&disk_map);
@@ -89,7 +116,8 @@ fn refomat_disk_single_address(mut disk_map: Vec<MemoryAddress>) -> Vec<MemoryAd
 
         match address {
             MemoryAddress::Empty => {
-                while disk_map[j] == MemoryAddress::Empty {
+                // A disk with no files left to the right is already compact
+                while j > i && disk_map[j] == MemoryAddress::Empty {
                     j -= 1;
                 }
                 if let MemoryAddress::FileNumber(_filenumber) = disk_map[j]
@@ -107,9 +135,9 @@ fn refomat_disk_single_address(mut disk_map: Vec<MemoryAddress>) -> Vec<MemoryAd
     disk_map
 }
 
-fn parse_to_memory_segmentation(data: &Vec<u16>) -> Vec<MemorySegment> {
+fn parse_to_memory_segmentation(data: &[usize]) -> Vec<MemorySegment> {
     let mut segment_map: Vec<MemorySegment> = Vec::new();
-    let mut filenumber: u16 = 0;
+    let mut filenumber: usize = 0;
     let mut is_file: bool = true;
 
     for entry in data.iter() {
@@ -131,81 +159,227 @@ fn parse_to_memory_segmentation(data: &Vec<u16>) -> Vec<MemorySegment> {
     segment_map
 }
 
-fn reformat_segmentation_map(mut segment_map: Vec<MemorySegment>) -> Vec<MemorySegment> {
-    let max_id = segment_map
-        .iter()
-        .filter_map(|s| s.filenumber)
-        .max()
-        .unwrap_or(0);
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AllocationStrategy {
+    FirstFit,
+    BestFit,
+    WorstFit,
+    NextFit,
+    Defragment,
+}
 
-    for id_to_move in (0..=max_id).rev() {
-        let file_idx = segment_map
-            .iter()
-            .position(|s| s.filenumber == Some(id_to_move))
-            .unwrap();
-        let file_len = segment_map[file_idx].length;
-
-        // Okay, so basically we work our way down from the highest-number
-        // file and look for the leftmost gap that would fit it -
-        let mut target_gap_idx = None;
-        for i in 0..file_idx {
-            if segment_map[i].filenumber.is_none() && segment_map[i].length >= file_len {
-                target_gap_idx = Some(i);
-                break;
+impl AllocationStrategy {
+    const ALL: [AllocationStrategy; 5] = [
+        AllocationStrategy::FirstFit,
+        AllocationStrategy::BestFit,
+        AllocationStrategy::WorstFit,
+        AllocationStrategy::NextFit,
+        AllocationStrategy::Defragment,
+    ];
+
+    fn from_name(name: &str) -> Option<AllocationStrategy> {
+        AllocationStrategy::ALL
+            .into_iter()
+            .find(|strategy| format!("{:?}", strategy).eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileMove {
+    filenumber: usize,
+    from: usize,
+    to: usize,
+    length: usize,
+}
+
+struct CompactionMetrics {
+    free_extents: usize,
+    largest_free_extent: usize,
+    files_moved: usize,
+    blocks_moved: usize,
+    checksum: u128,
+}
+
+// Whole-file compaction, working our way down from the highest file number
+// and only ever moving files to the left. Free space is indexed by gap
+// length: one ordered set of gap offsets per length, so each strategy finds
+// its gap in O(log n) instead of scanning the whole map.
+fn reformat_segmentation_map(
+    segment_map: Vec<MemorySegment>,
+    strategy: AllocationStrategy,
+) -> (Vec<MemorySegment>, Vec<FileMove>) {
+    let max_len = segment_map.iter().map(|s| s.length).max().unwrap_or(0);
+    let mut gaps: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); max_len + 1];
+    // (filenumber, offset, length), in increasing file number order
+    let mut files: Vec<(usize, usize, usize)> = Vec::new();
+    let mut moves: Vec<FileMove> = Vec::new();
+    let mut offset: usize = 0;
+
+    for segment in segment_map.iter() {
+        match segment.filenumber {
+            Some(filenumber) => files.push((filenumber, offset, segment.length)),
+            None if segment.length > 0 => {
+                gaps[segment.length].insert(offset);
             }
+            None => {}
         }
+        offset += segment.length;
+    }
+    let disk_size = offset;
 
-        // When we have a gap, we execute the transfer logic, which boils down to:
-        // (1) If the file and gap are equal in size, just swap
-        // (2) Otherwise, change the gap length to the file length and make the previous gap ID (None) the file ID
-        // (3) Mark the previous file position as Empty
-        // (4) inserting a new Empty segment on the next index to account for leftover space
-        if let Some(gap_idx) = target_gap_idx {
-            let gap_len = segment_map[gap_idx].length;
-
-            if gap_len == file_len {
-                segment_map[gap_idx].filenumber = Some(id_to_move);
-                segment_map[file_idx].filenumber = None;
-            } else {
-                segment_map[gap_idx].filenumber = Some(id_to_move);
-                segment_map[gap_idx].length = file_len;
-                segment_map[file_idx].filenumber = None;
-
-                segment_map.insert(
-                    gap_idx + 1,
-                    MemorySegment {
-                        filenumber: None,
-                        length: gap_len - file_len,
-                    },
-                );
+    if strategy == AllocationStrategy::Defragment {
+        // Close every gap: files keep their order and are packed from the start
+        files.sort_by_key(|&(_, offset, _)| offset);
+        let mut next_free: usize = 0;
+        for file in files.iter_mut() {
+            let (filenumber, file_offset, length) = *file;
+            if length > 0 && file_offset != next_free {
+                moves.push(FileMove {
+                    filenumber,
+                    from: file_offset,
+                    to: next_free,
+                    length,
+                });
+                file.1 = next_free;
             }
+            next_free += length;
         }
+        return (rebuild_segment_map(files, disk_size), moves);
     }
-    segment_map
+
+    // Next fit keeps searching from the end of the previous allocation
+    let mut rover: usize = 0;
+
+    for file in files.iter_mut().rev() {
+        let (filenumber, file_offset, file_len) = *file;
+        if file_len == 0 {
+            continue;
+        }
+
+        // Leftmost gap of each fitting length that lies in `range`
+        let candidates = |range: std::ops::Range<usize>| {
+            (file_len..=max_len)
+                .filter_map(|gap_len| {
+                    gaps[gap_len]
+                        .range(range.clone())
+                        .next()
+                        .map(|&gap_offset| (gap_offset, gap_len))
+                })
+                .collect::<Vec<(usize, usize)>>()
+        };
+
+        let target_gap = match strategy {
+            AllocationStrategy::FirstFit => candidates(0..file_offset).into_iter().min(),
+            AllocationStrategy::BestFit => candidates(0..file_offset).into_iter().next(),
+            AllocationStrategy::WorstFit => candidates(0..file_offset).into_iter().last(),
+            AllocationStrategy::NextFit => candidates(rover.min(file_offset)..file_offset)
+                .into_iter()
+                .min()
+                .or_else(|| candidates(0..rover.min(file_offset)).into_iter().min()),
+            AllocationStrategy::Defragment => unreachable!(),
+        };
+
+        // The space the file leaves behind is never reused: every file still
+        // to be moved sits further left and only moves left.
+        if let Some((gap_offset, gap_len)) = target_gap {
+            gaps[gap_len].remove(&gap_offset);
+            file.1 = gap_offset;
+            rover = gap_offset + file_len;
+            moves.push(FileMove {
+                filenumber,
+                from: file_offset,
+                to: gap_offset,
+                length: file_len,
+            });
+
+            let leftover = gap_len - file_len;
+            if leftover > 0 {
+                gaps[leftover].insert(gap_offset + file_len);
+            }
+        }
+    }
+    (rebuild_segment_map(files, disk_size), moves)
+}
+
+// Segment map in disk order, with gaps in between
+fn rebuild_segment_map(
+    mut files: Vec<(usize, usize, usize)>,
+    disk_size: usize,
+) -> Vec<MemorySegment> {
+    files.sort_by_key(|&(_, offset, _)| offset);
+    let mut compacted: Vec<MemorySegment> = Vec::new();
+    let mut offset: usize = 0;
+
+    for (filenumber, file_offset, length) in files {
+        push_gap(&mut compacted, file_offset.saturating_sub(offset));
+        compacted.push(MemorySegment {
+            filenumber: Some(filenumber),
+            length,
+        });
+        offset = offset.max(file_offset + length);
+    }
+    push_gap(&mut compacted, disk_size - offset);
+    compacted
 }
 
-fn calculate_checksum_diskmap(disk_map: &Vec<MemoryAddress>) -> u64 {
-    let mut checksum: u64 = 0;
+fn measure_compaction(segment_map: &[MemorySegment], moves: &[FileMove]) -> CompactionMetrics {
+    let mut free_extents = 0;
+    let mut largest_free_extent = 0;
+    let mut current_extent = 0;
+
+    for segment in segment_map.iter().filter(|s| s.length > 0) {
+        if segment.filenumber.is_none() {
+            if current_extent == 0 {
+                free_extents += 1;
+            }
+            current_extent += segment.length;
+            largest_free_extent = largest_free_extent.max(current_extent);
+        } else {
+            current_extent = 0;
+        }
+    }
+
+    CompactionMetrics {
+        free_extents,
+        largest_free_extent,
+        files_moved: moves.len(),
+        blocks_moved: moves.iter().map(|m| m.length).sum(),
+        checksum: calculate_checksum_segmentmap(segment_map),
+    }
+}
+
+fn push_gap(segment_map: &mut Vec<MemorySegment>, length: usize) {
+    if length > 0 {
+        segment_map.push(MemorySegment {
+            filenumber: None,
+            length,
+        });
+    }
+}
+
+// u128, since file numbers and positions both grow with the disk map length
+fn calculate_checksum_diskmap(disk_map: &[MemoryAddress]) -> u128 {
+    let mut checksum: u128 = 0;
 
     for (i, address) in disk_map.iter().enumerate() {
         match address {
             MemoryAddress::Empty => {}
             MemoryAddress::FileNumber(filenumber) => {
-                checksum += (*filenumber as u64) * (i as u64);
+                checksum += (*filenumber as u128) * (i as u128);
             }
         }
     }
     checksum
 }
 
-fn calculate_checksum_segmentmap(segment_map: &Vec<MemorySegment>) -> u64 {
-    let mut checksum: u64 = 0;
-    let mut idx: u64 = 0;
+fn calculate_checksum_segmentmap(segment_map: &[MemorySegment]) -> u128 {
+    let mut checksum: u128 = 0;
+    let mut idx: u128 = 0;
 
-    for (_i, segment) in segment_map.iter().enumerate() {
+    for segment in segment_map.iter() {
         for _j in 0..segment.length {
             if let Some(filenumber) = segment.filenumber {
-                checksum += idx * (filenumber as u64);
+                checksum += idx * (filenumber as u128);
             }
             idx += 1;
         }
@@ -213,15 +387,103 @@ fn calculate_checksum_segmentmap(segment_map: &Vec<MemorySegment>) -> u64 {
     checksum
 }
 
+impl FileMove {
+    // One move per line: filenumber from to length
+    fn to_line(self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.filenumber, self.from, self.to, self.length
+        )
+    }
+    fn from_line(line: &str) -> Result<FileMove, String> {
+        let fields: Vec<usize> = line
+            .split_whitespace()
+            .map(|field| field.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|err| format!("bad move {:?}: {}", line, err))?;
+        match fields[..] {
+            [filenumber, from, to, length] => Ok(FileMove {
+                filenumber,
+                from,
+                to,
+                length,
+            }),
+            _ => Err(format!("bad move {:?}: expected 4 numbers", line)),
+        }
+    }
+}
+
+// Moves a whole file block by block, refusing moves the disk doesn't allow
+fn apply_move(disk_map: &mut [MemoryAddress], file_move: &FileMove) -> Result<(), String> {
+    let FileMove {
+        filenumber,
+        from,
+        to,
+        length,
+    } = *file_move;
+    if from + length > disk_map.len() || to + length > disk_map.len() {
+        return Err(format!(
+            "move of file {} runs past the end of the disk",
+            filenumber
+        ));
+    }
+    if disk_map[from..from + length]
+        .iter()
+        .any(|a| *a != MemoryAddress::FileNumber(filenumber))
+    {
+        return Err(format!("file {} is not at offset {}", filenumber, from));
+    }
+    disk_map[from..from + length].fill(MemoryAddress::Empty);
+
+    if disk_map[to..to + length]
+        .iter()
+        .any(|a| *a != MemoryAddress::Empty)
+    {
+        return Err(format!("no room for file {} at offset {}", filenumber, to));
+    }
+    disk_map[to..to + length].fill(MemoryAddress::FileNumber(filenumber));
+    Ok(())
+}
+
+// Prints every move together with the disk state it leads to
+fn print_trace(data: &[usize], segment_map: &[MemorySegment], moves: &[FileMove]) {
+    let width = filenumber_width(segment_map);
+    let mut disk_map = expand_disk_map(data);
+
+    print_disk_map(&disk_map, width);
+    for file_move in moves {
+        apply_move(&mut disk_map, file_move).expect("Compaction produced an invalid move");
+        println!(
+            "Move file {} ({} blocks) from offset {} to offset {}",
+            file_move.filenumber, file_move.length, file_move.from, file_move.to
+        );
+        print_disk_map(&disk_map, width);
+    }
+}
+
+// Re-applies a saved trace to the original disk map and reports its checksum
+fn replay_trace(data: &[usize], trace: &str) -> Result<u128, String> {
+    let mut disk_map = expand_disk_map(data);
+
+    for (line_idx, line) in trace.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let file_move =
+            FileMove::from_line(line).map_err(|err| format!("line {}: {}", line_idx + 1, err))?;
+        apply_move(&mut disk_map, &file_move)
+            .map_err(|err| format!("line {}: {}", line_idx + 1, err))?;
+    }
+    Ok(calculate_checksum_diskmap(&disk_map))
+}
+
 fn main() {
     let data = read_disk_map();
 
     // Part I: "Naive" approach, every cell in memory is treated individually
     let mut disk_map = expand_disk_map(&data);
-    //print_disk_map(&disk_map);
 
     disk_map = refomat_disk_single_address(disk_map);
-    //print_disk_map(&disk_map);
 
     println!(
         "Checksum of file system (single address reordering): {}",
@@ -230,14 +492,164 @@ fn main() {
 
     // Part II: Parse data to a segmentation representation to make
     // swapping based on actual length easier.
-    let mut segment_map = parse_to_memory_segmentation(&data);
-    //print_segment_map(&segment_map);
+    let segment_map = parse_to_memory_segmentation(&data);
 
-    segment_map = reformat_segmentation_map(segment_map);
-    //print_segment_map(&segment_map);
+    let (compacted, _moves) =
+        reformat_segmentation_map(segment_map.clone(), AllocationStrategy::FirstFit);
 
     println!(
         "Checksum of file system (segmentation reordering): {}",
-        calculate_checksum_segmentmap(&segment_map)
+        calculate_checksum_segmentmap(&compacted)
     );
+
+    // Optional arguments:
+    //   trace [strategy] [file]  show every move, optionally saving the trace
+    //   replay <file>            re-apply a saved trace and check it
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("trace") => {
+            let mut rest = &args[1..];
+            let strategy = match rest
+                .first()
+                .and_then(|name| AllocationStrategy::from_name(name))
+            {
+                Some(strategy) => {
+                    rest = &rest[1..];
+                    strategy
+                }
+                None => AllocationStrategy::FirstFit,
+            };
+            let (compacted, moves) = reformat_segmentation_map(segment_map.clone(), strategy);
+            print_trace(&data, &segment_map, &moves);
+            print_segment_map(&compacted, filenumber_width(&compacted));
+
+            if let Some(path) = rest.first() {
+                let trace: String = moves.iter().map(|m| m.to_line() + "\n").collect();
+                fs::write(path, trace).expect("Could not write the trace file");
+                println!(
+                    "{:?} trace with {} moves written to {}",
+                    strategy,
+                    moves.len(),
+                    path
+                );
+            }
+        }
+        Some("replay") => {
+            let path = args.get(1).expect("Usage: replay <trace file>");
+            let trace = fs::read_to_string(path).expect("Could not read the trace file");
+            match replay_trace(&data, &trace) {
+                Ok(checksum) => println!("Replayed trace from {}, checksum: {}", path, checksum),
+                Err(err) => println!("Trace from {} does not apply: {}", path, err),
+            }
+        }
+        _ => {}
+    }
+
+    // Compare all allocation strategies on the same disk map
+    println!(
+        "\n{:<12}{:>14}{:>16}{:>13}{:>14}{:>22}",
+        "Strategy", "Free extents", "Largest extent", "Files moved", "Blocks moved", "Checksum"
+    );
+    for strategy in AllocationStrategy::ALL {
+        let (compacted, moves) = reformat_segmentation_map(segment_map.clone(), strategy);
+        let metrics = measure_compaction(&compacted, &moves);
+        println!(
+            "{:<12}{:>14}{:>16}{:>13}{:>14}{:>22}",
+            format!("{:?}", strategy),
+            metrics.free_extents,
+            metrics.largest_free_extent,
+            metrics.files_moved,
+            metrics.blocks_moved,
+            metrics.checksum
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "2333133121414131402";
+
+    // Checksum straight from the digits: file `id` of length `l` starting at
+    // block `s` adds id * (s + s+1 + ... + s+l-1)
+    fn direct_checksum(data: &[usize]) -> u128 {
+        let mut checksum = 0;
+        let mut start = 0;
+        for (i, &length) in data.iter().enumerate() {
+            let (s, l) = (start as u128, length as u128);
+            if i % 2 == 0 {
+                checksum += (i / 2) as u128 * (l * s + l * l.saturating_sub(1) / 2);
+            }
+            start += length;
+        }
+        checksum
+    }
+
+    #[test]
+    fn checksums_beyond_u16_file_numbers() {
+        let files = 70_000;
+        let input: String = (0..files * 2 - 1)
+            .map(|i| char::from(b'0' + ((i * 7 + 3) % 9 + 1) as u8))
+            .collect();
+        let data = parse_disk_map(&input).unwrap();
+        let expected = direct_checksum(&data);
+
+        assert!(data.len().div_ceil(2) > u16::MAX as usize);
+        assert_eq!(
+            calculate_checksum_diskmap(&expand_disk_map(&data)),
+            expected
+        );
+        assert_eq!(
+            calculate_checksum_segmentmap(&parse_to_memory_segmentation(&data)),
+            expected
+        );
+    }
+
+    #[test]
+    fn example_checksums_for_block_and_file_compaction() {
+        let data = parse_disk_map(EXAMPLE).unwrap();
+        let blocks = refomat_disk_single_address(expand_disk_map(&data));
+        assert_eq!(calculate_checksum_diskmap(&blocks), 1928);
+
+        let (compacted, _) = reformat_segmentation_map(
+            parse_to_memory_segmentation(&data),
+            AllocationStrategy::FirstFit,
+        );
+        assert_eq!(calculate_checksum_segmentmap(&compacted), 2858);
+
+        // The same layout spelled out block by block gives the same checksum
+        let compacted_blocks: Vec<MemoryAddress> = compacted
+            .iter()
+            .flat_map(|segment| {
+                let address = match segment.filenumber {
+                    Some(filenumber) => MemoryAddress::FileNumber(filenumber),
+                    None => MemoryAddress::Empty,
+                };
+                std::iter::repeat_n(address, segment.length)
+            })
+            .collect();
+        assert_eq!(calculate_checksum_diskmap(&compacted_blocks), 2858);
+    }
+
+    #[test]
+    fn compaction_without_file_blocks() {
+        for input in ["0", "02", "0000"] {
+            let data = parse_disk_map(input).unwrap();
+            let blocks = refomat_disk_single_address(expand_disk_map(&data));
+            assert_eq!(calculate_checksum_diskmap(&blocks), 0);
+            for strategy in AllocationStrategy::ALL {
+                let (compacted, moves) =
+                    reformat_segmentation_map(parse_to_memory_segmentation(&data), strategy);
+                assert_eq!(calculate_checksum_segmentmap(&compacted), 0);
+                assert!(moves.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn parse_disk_map_reports_position() {
+        let err = parse_disk_map("12345\n67x9").unwrap_err();
+        assert_eq!(err, "unexpected character 'x' at line 2, column 3");
+    }
 }